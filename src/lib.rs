@@ -1,31 +1,716 @@
-use bevy::app::{AppBuilder, Plugin};
+use bevy::app::{AppBuilder, Events, Plugin};
 use bevy::prelude::*;
-use bevy::reflect::*;
+use bevy::reflect::{TypeUuid, Uuid};
+use bevy::render::mesh::Indices;
+use bevy::render::pipeline::PrimitiveTopology;
+use bevy::render::texture::{AddressMode, FilterMode, SamplerDescriptor};
+
+use bevy::asset::Asset;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 mod loader;
-use loader::ObjLoader;
+mod material;
+mod memory;
+mod writer;
+pub use loader::{
+    load_obj_from_bytes_blocking, parse_obj, shininess_to_roughness, ObjError, ParsedMesh,
+    ParsedObj,
+};
+use loader::{MtlLoader, ObjLoader};
+use material::add_pipelines;
+pub use material::{
+    specular_to_reflectance, MaterialContext, MaterialConverter, StandardMaterialConverter,
+    TextureSlot, DOUBLE_SIDED_PIPELINE_HANDLE, DOUBLE_SIDED_VERTEX_COLOR_PIPELINE_HANDLE,
+    VERTEX_COLOR_PIPELINE_HANDLE,
+};
+pub use memory::{MemoryAssetIo, MemoryAssetPlugin, MemoryAssets};
+pub use writer::write_obj;
+// Material converters are handed the parsed `tobj` materials.
+pub use tobj;
 
 #[derive(Default)]
-pub struct ObjPlugin;
+pub struct ObjPlugin {
+    pub settings: ObjLoaderSettings,
+    /// Settings for the files whose asset path matches a pattern, where `*` matches any run of
+    /// characters, like `scans/*` or `*_zup.obj`. The first match is used instead of `settings`.
+    pub path_settings: Vec<(String, ObjLoaderSettings)>,
+    /// Log a summary of the `ObjLoadStats` of every file when it is loaded or reloaded.
+    pub log_stats: bool,
+}
+
+impl ObjPlugin {
+    /// Loads `.obj` files with a custom material converter, as `Obj<C::Material>` rendered with
+    /// `C::Bundle`. The material asset itself must be registered by the app. Standalone `.mtl`
+    /// files are still loaded as `StandardMaterial`s.
+    pub fn with_material_converter<C: MaterialConverter>(
+        self,
+        converter: C,
+    ) -> ObjMaterialPlugin<C> {
+        ObjMaterialPlugin {
+            settings: self.settings,
+            path_settings: self.path_settings,
+            log_stats: self.log_stats,
+            converter: Arc::new(converter),
+        }
+    }
+}
 
 impl Plugin for ObjPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.init_asset_loader::<ObjLoader>()
+        let path_settings = self.path_settings.clone();
+        app.add_asset_loader(ObjLoader::new(self.settings.clone(), path_settings.clone()))
+            .add_asset_loader(MtlLoader::new(self.settings.clone(), path_settings))
             .add_asset::<Obj>()
-            .add_asset::<ObjMesh>();
+            .add_asset::<ObjMesh>()
+            .add_asset::<MtlLib>()
+            .add_asset::<ObjLoadStats>()
+            .add_event::<ObjLoadedEvent>()
+            .register_type::<ObjTextureTransform>()
+            .register_type::<ObjMaterialName>()
+            .add_startup_system(add_pipelines.system())
+            .add_system(obj_loaded_events::<StandardMaterial>.system())
+            .add_system(apply_material_overrides::<StandardMaterial>.system());
+        if self.log_stats {
+            app.add_system(log_obj_stats::<StandardMaterial>.system());
+        }
+    }
+}
+
+/// `ObjPlugin` with a custom `MaterialConverter`, see `ObjPlugin::with_material_converter`.
+pub struct ObjMaterialPlugin<C> {
+    pub settings: ObjLoaderSettings,
+    pub path_settings: Vec<(String, ObjLoaderSettings)>,
+    pub log_stats: bool,
+    converter: Arc<C>,
+}
+
+impl<C: MaterialConverter> Plugin for ObjMaterialPlugin<C> {
+    fn build(&self, app: &mut AppBuilder) {
+        let path_settings = self.path_settings.clone();
+        let loader = ObjLoader::with_converter(
+            self.settings.clone(),
+            path_settings.clone(),
+            self.converter.clone(),
+        );
+        app.add_asset_loader(loader)
+            .add_asset_loader(MtlLoader::new(self.settings.clone(), path_settings))
+            .add_asset::<Obj<C::Material>>()
+            .add_asset::<ObjMesh<C::Material>>()
+            .add_asset::<MtlLib>()
+            .add_asset::<ObjLoadStats>()
+            .add_event::<ObjLoadedEvent<C::Material>>()
+            .register_type::<ObjTextureTransform>()
+            .register_type::<ObjMaterialName>()
+            .add_startup_system(add_pipelines.system())
+            .add_system(obj_loaded_events::<C::Material>.system())
+            .add_system(apply_material_overrides::<C::Material>.system());
+        if self.log_stats {
+            app.add_system(log_obj_stats::<C::Material>.system());
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjLoaderSettings {
+    /// Sampler used for all loaded textures. Defaults to repeating, linearly filtered textures,
+    /// which is what MTL files assume. Use nearest filtering for pixel art.
+    pub sampler: SamplerDescriptor,
+    /// Split quads and n-gons into triangles while parsing, ear clipping the concave ones. Meshes
+    /// are built as triangle lists, so this should only be disabled when the indices are
+    /// post-processed into another topology.
+    pub triangulate: bool,
+    /// OBJ texture coordinates have a bottom-left origin while Bevy samples from the top-left,
+    /// so the V coordinate is flipped by default to match assets loaded through gltf.
+    pub flip_uv_v: bool,
+    /// Apply the `-o` offset and `-s` scale of `map_Kd` statements to the texture coordinates
+    /// of the meshes using them, instead of leaving them to custom shaders through
+    /// `ObjTextureTransform`. Every texture of a material is then sampled with the transform.
+    pub bake_texture_transform: bool,
+    /// Base color of the material given to meshes that don't reference one.
+    pub default_base_color: Color,
+    /// Material given to meshes that don't reference one instead of a plain `default_base_color`
+    /// one, loaded once under the `DefaultMaterial` label. When set, it is also assigned to the
+    /// `ObjMesh` of those meshes. Lines and points keep their unlit fallback.
+    pub default_material: Option<Arc<StandardMaterial>>,
+    /// Follow the `illum` statements of MTL materials: `illum 0` and `illum 1` materials are
+    /// unlit, and the reflective models from `illum 3` on get full reflectance while keeping the
+    /// metallic of their `Ks` or `Pm`. Disable to shade every material as lit PBR.
+    pub illumination_models: bool,
+    /// How the `Ns` specular exponent of materials without a `Pr` is turned into roughness.
+    pub shininess_conversion: ShininessConversion,
+    /// Render the faces of every material from both sides instead of culling their back faces,
+    /// e.g. for models built from single-sided walls. Materials with a `map_d` alpha map, as used
+    /// for foliage and fences cut out of cards, are always double sided.
+    pub double_sided: bool,
+    /// Merge models into fewer meshes to cut down on entities and draw calls for static geometry
+    /// made of many small objects.
+    pub mesh_merging: MeshMerging,
+    /// Uniform scale applied to the vertex positions, e.g. `0.01` for models authored in
+    /// centimeters. Negative scales mirror the model and reverse the winding to match.
+    pub scale: f32,
+    /// Up axis the model was authored with. Bevy is Y-up, so other axes are converted in the
+    /// vertex data.
+    pub up_axis: UpAxis,
+    /// Put the `scale` and `up_axis` conversion on the `Transform` of the scene's root entity
+    /// instead of the vertex data, where it can be read back or undone. The meshes, their bounds
+    /// and anything built from them, like colliders, then stay in the units and axes of the
+    /// file. Bevy renders a negative scale on the root inside out, like any mirroring
+    /// `Transform`, so mirrored models are best left to the vertex data. Ignored along with
+    /// `meshes_only`, which builds no scene.
+    pub transform_root: bool,
+    /// Reverse the winding of every face, for exporters writing clockwise faces that Bevy culls
+    /// as back faces. Generated normals follow the new winding, while normals given by the file
+    /// are kept.
+    pub flip_winding: bool,
+    /// Turn around every triangle whose winding disagrees with the normals the file gives for
+    /// its corners, fixing mirrored parts that render inside out. Costs a pass over every
+    /// triangle, and does nothing for files without normals.
+    pub fix_winding: bool,
+    /// Only load the meshes, e.g. for collision geometry that is never rendered. Material
+    /// libraries and textures are not read and no `Scene` is built, leaving just the `Mesh` and
+    /// `ObjMesh` labels plus `Obj`.
+    pub meshes_only: bool,
+    /// How models whose attributes don't line up with their positions, or whose indices are out
+    /// of range, are handled.
+    pub validation: Validation,
+    /// Merge vertices whose position, normal and texcoord are exactly the same, shrinking meshes
+    /// from exporters that give every face its own copies of shared corners.
+    pub dedup_vertices: bool,
+    /// How normals are generated for triangles of files without `vn` lines.
+    pub generated_normals: GeneratedNormals,
+    /// Share one `Mesh` between objects with exactly the same vertex data and indices, like a
+    /// prop copied around a level. Every object keeps its own `ObjMesh`, scene entity and
+    /// `Mesh{i}` label, but the copies get no named `Mesh` label of their own.
+    pub instance_meshes: bool,
+    /// Also store every mesh under its position in the file, as `Mesh0`, `Mesh1` and so on, and
+    /// a file with a single model as the default asset, to load meshes without knowing the
+    /// object names. Bevy can't point two labels at one asset, so these are copies of the meshes
+    /// the scene uses, each with buffers of its own. Turn it off to keep one copy of large files.
+    pub mesh_index_labels: bool,
+    /// Fail the load when a texture can't be read or decoded. By default such textures are left
+    /// out of their materials, or replaced by a magenta checker when they don't decode.
+    pub strict_textures: bool,
+    /// Fail the load when a material library can't be parsed. By default such libraries are
+    /// skipped like missing ones, leaving their meshes with the default material.
+    pub strict_materials: bool,
+    /// Color spaces to sample the textures of some slots in instead of their defaults, e.g.
+    /// `(TextureSlot::Specular, ColorSpace::Srgb)` for specular maps painted as colors.
+    pub texture_color_spaces: Vec<(TextureSlot, ColorSpace)>,
+    /// Object names to put in the scene, as patterns where `*` matches any run of characters.
+    /// When empty, every object is included.
+    pub include: Vec<String>,
+    /// Object names to leave out of the scene, e.g. `collision_*` for helper geometry, taking
+    /// precedence over `include`. Excluded objects still get their `Mesh` and `ObjMesh` labels
+    /// and are listed in `Obj::excluded_meshes`.
+    pub exclude: Vec<String>,
+    /// Whether excluded objects are left out of the scene or spawned hidden.
+    pub excluded_meshes: ExcludedMeshes,
+}
+
+impl Default for ObjLoaderSettings {
+    fn default() -> Self {
+        ObjLoaderSettings {
+            sampler: SamplerDescriptor {
+                address_mode_u: AddressMode::Repeat,
+                address_mode_v: AddressMode::Repeat,
+                mag_filter: FilterMode::Linear,
+                min_filter: FilterMode::Linear,
+                mipmap_filter: FilterMode::Linear,
+                ..Default::default()
+            },
+            triangulate: true,
+            flip_uv_v: true,
+            bake_texture_transform: false,
+            default_base_color: Color::WHITE,
+            default_material: None,
+            illumination_models: true,
+            shininess_conversion: ShininessConversion::BlinnPhong,
+            double_sided: false,
+            mesh_merging: MeshMerging::None,
+            scale: 1.0,
+            up_axis: UpAxis::Y,
+            transform_root: false,
+            flip_winding: false,
+            fix_winding: false,
+            meshes_only: false,
+            validation: Validation::Lenient,
+            dedup_vertices: true,
+            generated_normals: GeneratedNormals::Smooth,
+            instance_meshes: false,
+            mesh_index_labels: true,
+            strict_textures: false,
+            strict_materials: false,
+            texture_color_spaces: Vec::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            excluded_meshes: ExcludedMeshes::Skip,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshMerging {
+    /// Keep one mesh per model in the file.
+    None,
+    /// Merge all models sharing a material into one mesh per material.
+    ByMaterial,
+    /// Merge every model into a single mesh. Materials can't be merged, so the combined mesh
+    /// uses the material of the first model and any others are dropped.
+    All,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Validation {
+    /// Log a warning and repair the model: surplus attributes are truncated, missing texcoords
+    /// are padded, mismatched normals are regenerated, and faces with fewer than three corners
+    /// or out of range indices are dropped, as are primitives using non-finite positions.
+    Lenient,
+    /// Fail the load naming the model, with `ObjError::MalformedVertexData` for attributes that
+    /// don't match the positions and `ObjError::Validation` for bad indices.
+    Strict,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneratedNormals {
+    /// Average the normals of the faces sharing a vertex, weighted by their area, honoring the
+    /// file's smoothing groups.
+    Smooth,
+    /// Give every face its own normal, for a faceted look. Faces no longer share vertices.
+    Flat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Decode the texels from sRGB, as for images holding colors.
+    Srgb,
+    /// Sample the texels as they are, as for images holding data. Normal maps stay normal maps.
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ShininessConversion {
+    /// Match the highlight of a Blinn-Phong exponent, see `shininess_to_roughness`.
+    BlinnPhong,
+    /// Undo Blender's OBJ exporter, which writes `Ns = (1 - roughness)^2 * 1000`. Its files look
+    /// far too glossy with `BlinnPhong`.
+    Blender,
+    /// Convert with a function of its own, given the exponent as written in the file.
+    Custom(fn(f32) -> f32),
+}
+
+impl ShininessConversion {
+    pub fn roughness(self, shininess: f32) -> f32 {
+        match self {
+            ShininessConversion::BlinnPhong => shininess_to_roughness(shininess),
+            ShininessConversion::Blender => 1.0 - (shininess / 1000.0).clamp(0.0, 1.0).sqrt(),
+            ShininessConversion::Custom(convert) => convert(shininess).clamp(0.0, 1.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExcludedMeshes {
+    /// Leave excluded objects out of the scene.
+    Skip,
+    /// Spawn excluded objects with `Visible::is_visible` set to false.
+    Hide,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpAxis {
+    /// Y-up, as used by Bevy. No conversion is applied.
+    Y,
+    /// Z-up, as exported by Blender and most CAD tools. Positions and normals are rotated to Y-up.
+    Z,
+}
+
+#[derive(Debug)]
+pub struct Obj<M: Asset = StandardMaterial> {
+    /// Materials in the order the file first uses them, followed by any unused ones in the order
+    /// they are defined.
+    pub materials: Vec<Handle<M>>,
+    pub meshes: Vec<Handle<ObjMesh<M>>>,
+    /// The meshes of objects excluded from the scene by `ObjLoaderSettings::exclude` or
+    /// `ObjLoaderSettings::include`.
+    pub excluded_meshes: Vec<Handle<ObjMesh<M>>>,
+    /// The scene spawning every mesh, unless only meshes were loaded.
+    pub scene: Option<Handle<Scene>>,
+    /// The `Stats` of the file.
+    pub stats: Handle<ObjLoadStats>,
+    /// Bounds of every vertex in the file, collapsed to the origin when there are none.
+    pub aabb_min: Vec3,
+    pub aabb_max: Vec3,
+}
+
+impl<M: Asset> Obj<M> {
+    /// Spawns an instance of the scene as the child of a new entity placed at `transform`, and
+    /// returns that entity, or `None` when only meshes were loaded. Every instance shares the
+    /// meshes and materials of the `Obj`, so spawning many copies of a model loads nothing new.
+    pub fn spawn(&self, commands: &mut Commands, transform: Transform) -> Option<Entity> {
+        let scene = self.scene.clone()?;
+        let entity = commands
+            .spawn_bundle((transform, GlobalTransform::identity()))
+            .with_children(|parent| {
+                parent.spawn_scene(scene);
+            })
+            .id();
+        Some(entity)
+    }
+
+    /// The mesh of the object named `name`, as found in `ObjMesh::name`.
+    pub fn mesh_by_name(
+        &self,
+        obj_meshes: &Assets<ObjMesh<M>>,
+        name: &str,
+    ) -> Option<Handle<ObjMesh<M>>> {
+        self.meshes
+            .iter()
+            .find(|handle| {
+                obj_meshes
+                    .get(*handle)
+                    .is_some_and(|mesh| mesh.name == name)
+            })
+            .cloned()
+    }
+
+    /// The material named `name` in its MTL file, or by its full label like
+    /// `materials.mtl/Red` to tell apart libraries defining the same name. Only materials used by
+    /// a mesh are found.
+    pub fn material_by_name(
+        &self,
+        obj_meshes: &Assets<ObjMesh<M>>,
+        name: &str,
+    ) -> Option<Handle<M>> {
+        self.meshes.iter().find_map(|handle| {
+            let mesh = obj_meshes.get(handle)?;
+            let label = mesh.material_name.as_deref()?;
+            let matches = label == name
+                || label
+                    .strip_suffix(name)
+                    .is_some_and(|library| library.ends_with('/'));
+            if matches {
+                mesh.material.clone()
+            } else {
+                None
+            }
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ObjMesh<M: Asset = StandardMaterial> {
+    /// The object's name, made unique within the file, which is also the label of its mesh.
+    pub name: String,
+    pub mesh: Handle<Mesh>,
+    /// The label of the MTL material the object uses, like `materials.mtl/Red`.
+    pub material_name: Option<String>,
+    pub material: Option<Handle<M>>,
+    /// The `Ka` ambient color of the material, unless black. PBR has no ambient term to put it
+    /// in, as Bevy lights the whole scene with one `AmbientLight`, so the materials don't use it.
+    /// It is kept here for those who want, for instance, a glow floor against black shadows.
+    pub ambient_color: Option<Color>,
+    /// Offset and scale of the material's diffuse texture, also added to the mesh's entity in
+    /// the scene.
+    pub texture_transform: Option<ObjTextureTransform>,
+    /// Bounds of the mesh's vertices, collapsed to the origin when there are none.
+    pub aabb_min: Vec3,
+    pub aabb_max: Vec3,
+}
+
+/// The `-o` offset and `-s` scale options of a `map_Kd` statement, which Bevy's materials can't
+/// apply to texture coordinates themselves. Not inserted when
+/// `ObjLoaderSettings::bake_texture_transform` applied them to the mesh already.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct ObjTextureTransform {
+    pub offset: Vec3,
+    pub scale: Vec3,
+}
+
+/// The label of the MTL material a mesh entity of an `Obj` scene was given, like
+/// `materials.mtl/Red`, by which `ObjMaterialOverride` finds it.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Reflect)]
+#[reflect(Component)]
+pub struct ObjMaterialName(pub String);
+
+/// Replaces the materials of the `Obj` scenes spawned below the entity it is added to, keyed by
+/// the `ObjMaterialName` of their mesh entities, e.g. to tint every instance of a model in its
+/// own color. Meshes whose material isn't overridden keep the one of the `Obj`, and removing the
+/// component leaves the overrides in place.
+pub struct ObjMaterialOverride<M: Asset = StandardMaterial>(pub HashMap<String, Handle<M>>);
+
+impl<M: Asset> TypeUuid for Obj<M> {
+    const TYPE_UUID: Uuid =
+        material_uuid::<M>(Uuid::from_u128(0xa4de4700_f615_4910_bc86_84f9a24ce5ae));
+}
+
+impl<M: Asset> TypeUuid for ObjMesh<M> {
+    const TYPE_UUID: Uuid =
+        material_uuid::<M>(Uuid::from_u128(0xa01f5ccf_0db1_4577_a951_c8749caa5b4e));
+}
+
+// Every material type needs asset types of its own, so their UUIDs are mixed with the material's
+// in a way that leaves the ones for `StandardMaterial` unchanged.
+const fn material_uuid<M: TypeUuid>(base: Uuid) -> Uuid {
+    let base = base.as_bytes();
+    let material = M::TYPE_UUID;
+    let material = material.as_bytes();
+    let standard = StandardMaterial::TYPE_UUID;
+    let standard = standard.as_bytes();
+    let mut bytes = [0; 16];
+    let mut i = 0;
+    while i < bytes.len() {
+        bytes[i] = base[i] ^ material[i] ^ standard[i];
+        i += 1;
     }
+    Uuid::from_bytes(bytes)
+}
+
+/// What went into an `Obj`, stored under the `Stats` label for profiling and debugging.
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "0b7e3c52-91d4-4f6a-8a1e-2d5c7f9b3e64"]
+pub struct ObjLoadStats {
+    pub object_count: usize,
+    pub vertex_count: usize,
+    pub triangle_count: usize,
+    pub material_count: usize,
+    /// Textures loaded for the materials, counting an image used in several color spaces or with
+    /// different samplers once per variant.
+    pub texture_count: usize,
+    /// Bytes of pixel data of the textures, after their conversion to the formats Bevy uploads,
+    /// which is four bytes per pixel for most images.
+    pub texture_memory: usize,
+    /// Whether the file gives normals, or they were generated.
+    pub has_normals: bool,
+    /// Whether the file gives texture coordinates, or they were zeroed.
+    pub has_texcoords: bool,
+    /// Vertex count of every mesh, in the order of `Obj::meshes`.
+    pub mesh_vertex_counts: Vec<usize>,
 }
 
+/// A material library loaded from a `.mtl` file on its own.
 #[derive(Debug, TypeUuid)]
-#[uuid = "a4de4700-f615-4910-bc86-84f9a24ce5ae"]
-pub struct Obj {
+#[uuid = "5c2b4f3e-8d61-4a3b-9f27-6e0c1d84b7a2"]
+pub struct MtlLib {
     pub materials: Vec<Handle<StandardMaterial>>,
-    pub meshes: Vec<Handle<ObjMesh>>,
 }
 
-#[derive(Debug, TypeUuid)]
-#[uuid = "a01f5ccf-0db1-4577-a951-c8749caa5b4e"]
-pub struct ObjMesh {
-    pub mesh: Handle<Mesh>,
-    pub material: Option<Handle<StandardMaterial>>,
+/// Sent once an `Obj` and its meshes have been added to their asset collections, and again every
+/// time the file is reloaded.
+#[derive(Debug, Clone)]
+pub struct ObjLoadedEvent<M: Asset = StandardMaterial> {
+    pub obj: Handle<Obj<M>>,
+    pub scene: Option<Handle<Scene>>,
+    pub mesh_count: usize,
+    pub material_count: usize,
+    pub vertex_count: usize,
+    pub triangle_count: usize,
+    /// Whether this is a hot reload of an already loaded file.
+    pub reloaded: bool,
+}
+
+fn obj_loaded_events<M: Asset>(
+    mut asset_events: EventReader<AssetEvent<Obj<M>>>,
+    mut loaded_events: ResMut<Events<ObjLoadedEvent<M>>>,
+    objs: Res<Assets<Obj<M>>>,
+    obj_meshes: Res<Assets<ObjMesh<M>>>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    for event in asset_events.iter() {
+        let (handle, reloaded) = match event {
+            AssetEvent::Created { handle } => (handle, false),
+            AssetEvent::Modified { handle } => (handle, true),
+            AssetEvent::Removed { .. } => continue,
+        };
+        let obj = match objs.get(handle) {
+            Some(obj) => obj,
+            None => continue,
+        };
+
+        let mut vertex_count = 0;
+        let mut triangle_count = 0;
+        for obj_mesh in &obj.meshes {
+            let mesh = match obj_meshes.get(obj_mesh).and_then(|m| meshes.get(&m.mesh)) {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            vertex_count += mesh.count_vertices();
+            if mesh.primitive_topology() == PrimitiveTopology::TriangleList {
+                let index_count = match mesh.indices() {
+                    Some(Indices::U16(indices)) => indices.len(),
+                    Some(Indices::U32(indices)) => indices.len(),
+                    None => mesh.count_vertices(),
+                };
+                triangle_count += index_count / 3;
+            }
+        }
+
+        loaded_events.send(ObjLoadedEvent {
+            obj: handle.clone(),
+            scene: obj.scene.clone(),
+            mesh_count: obj.meshes.len(),
+            material_count: obj.materials.len(),
+            vertex_count,
+            triangle_count,
+            reloaded,
+        });
+    }
+}
+
+fn apply_material_overrides<M: Asset>(
+    changed_overrides: Query<Entity, Changed<ObjMaterialOverride<M>>>,
+    spawned_meshes: Query<Entity, Added<ObjMaterialName>>,
+    overrides: Query<&ObjMaterialOverride<M>>,
+    parents: Query<&Parent>,
+    children: Query<&Children>,
+    mut materials: Query<(&ObjMaterialName, &mut Handle<M>)>,
+) {
+    // Scenes are spawned a few frames after they are requested, so meshes look for an override
+    // above them once they appear.
+    for entity in spawned_meshes.iter() {
+        let mut ancestor = entity;
+        loop {
+            if let Ok(material_override) = overrides.get(ancestor) {
+                override_material(entity, material_override, &mut materials);
+                break;
+            }
+            match parents.get(ancestor) {
+                Ok(parent) => ancestor = parent.0,
+                Err(_) => break,
+            }
+        }
+    }
+
+    // A new or changed override applies to everything already below it, except what is covered
+    // by an override of its own.
+    for root in changed_overrides.iter() {
+        let material_override = match overrides.get(root) {
+            Ok(material_override) => material_override,
+            Err(_) => continue,
+        };
+        let mut stack = vec![root];
+        while let Some(entity) = stack.pop() {
+            if entity != root && overrides.get(entity).is_ok() {
+                continue;
+            }
+            override_material(entity, material_override, &mut materials);
+            if let Ok(entity_children) = children.get(entity) {
+                stack.extend(entity_children.iter().copied());
+            }
+        }
+    }
+}
+
+fn override_material<M: Asset>(
+    entity: Entity,
+    material_override: &ObjMaterialOverride<M>,
+    materials: &mut Query<(&ObjMaterialName, &mut Handle<M>)>,
+) {
+    if let Ok((name, mut material)) = materials.get_mut(entity) {
+        if let Some(replacement) = material_override.0.get(&name.0) {
+            *material = replacement.clone();
+        }
+    }
+}
+
+fn log_obj_stats<M: Asset>(
+    mut loaded_events: EventReader<ObjLoadedEvent<M>>,
+    asset_server: Res<AssetServer>,
+    objs: Res<Assets<Obj<M>>>,
+    stats: Res<Assets<ObjLoadStats>>,
+) {
+    for event in loaded_events.iter() {
+        let stats = match objs.get(&event.obj).and_then(|obj| stats.get(&obj.stats)) {
+            Some(stats) => stats,
+            None => continue,
+        };
+        let path = asset_server
+            .get_handle_path(&event.obj)
+            .map(|path| path.path().display().to_string())
+            .unwrap_or_default();
+        info!(
+            "{}{}: {} objects, {} vertices, {} triangles, {} materials, {} textures ({} KiB){}{}",
+            path,
+            if event.reloaded { " (reloaded)" } else { "" },
+            stats.object_count,
+            stats.vertex_count,
+            stats.triangle_count,
+            stats.material_count,
+            stats.texture_count,
+            stats.texture_memory / 1024,
+            if stats.has_normals {
+                ""
+            } else {
+                ", generated normals"
+            },
+            if stats.has_texcoords {
+                ""
+            } else {
+                ", no texcoords"
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::HandleId;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    fn material() -> Handle<StandardMaterial> {
+        Handle::weak(HandleId::random::<StandardMaterial>())
+    }
+
+    fn spawn_mesh(
+        world: &mut World,
+        root: Entity,
+        name: &str,
+        material: &Handle<StandardMaterial>,
+    ) -> Entity {
+        let mesh = world
+            .spawn()
+            .insert_bundle((ObjMaterialName(name.to_string()), material.clone()))
+            .id();
+        world.entity_mut(root).push_children(&[mesh]);
+        mesh
+    }
+
+    #[test]
+    fn overrides_only_the_named_materials() {
+        let (red, blue, tint) = (material(), material(), material());
+        let mut world = World::default();
+        let root = world
+            .spawn()
+            .insert(ObjMaterialOverride(
+                vec![("materials.mtl/Red".to_string(), tint.clone())]
+                    .into_iter()
+                    .collect(),
+            ))
+            .id();
+        let red_mesh = spawn_mesh(&mut world, root, "materials.mtl/Red", &red);
+        let blue_mesh = spawn_mesh(&mut world, root, "materials.mtl/Blue", &blue);
+
+        let mut stage = SystemStage::single(apply_material_overrides::<StandardMaterial>.system());
+        stage.run(&mut world);
+        assert_eq!(world.get::<Handle<StandardMaterial>>(red_mesh), Some(&tint));
+        assert_eq!(
+            world.get::<Handle<StandardMaterial>>(blue_mesh),
+            Some(&blue)
+        );
+
+        // Meshes spawned after the override, like those of a scene that finished loading late,
+        // find it by themselves.
+        let late_mesh = spawn_mesh(&mut world, root, "materials.mtl/Red", &red);
+        stage.run(&mut world);
+        assert_eq!(
+            world.get::<Handle<StandardMaterial>>(late_mesh),
+            Some(&tint)
+        );
+        assert_eq!(
+            world.get::<Handle<StandardMaterial>>(blue_mesh),
+            Some(&blue)
+        );
+    }
 }
@@ -1,291 +1,4578 @@
-use anyhow::{Context, Result};
-use bevy::asset::{Asset, AssetLoader, AssetPath, BoxedFuture, LoadContext, LoadedAsset};
+use anyhow::{bail, Result};
+use bevy::asset::{AssetIoError, AssetLoader, AssetPath, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::log::{debug, warn};
 
-use bevy::pbr::PbrBundle;
 use bevy::prelude::{
-    BuildWorldChildren, Color, GlobalTransform, Handle, Mat4, Mesh, StandardMaterial, Texture,
-    Transform, World,
+    BuildWorldChildren, Color, GlobalTransform, Handle, Mesh, Name, Quat, Texture, Transform, Vec3,
+    Visible, World,
 };
 use bevy::render::mesh::{Indices, VertexAttributeValues};
 use bevy::render::pipeline::PrimitiveTopology;
-use bevy::render::texture::{FilterMode, ImageType, SamplerDescriptor, TextureFormat};
+use bevy::render::texture::{
+    AddressMode, Extent3d, FilterMode, ImageType, TextureDimension, TextureFormat,
+};
 use bevy::scene::Scene;
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::convert::{TryFrom, TryInto};
+use flate2::read::GzDecoder;
+use futures_lite::future;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::future::Future;
-use std::io::{BufRead, BufReader, ErrorKind};
-use std::path::Path;
-use std::rc::Rc;
-use std::sync::{Arc, Mutex};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Poll;
 use thiserror::Error;
-use tobj::{LoadError, MTLLoadResult};
+
+use super::material::{MaterialContext, MaterialConverter, StandardMaterialConverter, TextureSlot};
+use super::{
+    ExcludedMeshes, GeneratedNormals, MeshMerging, ObjLoaderSettings, ObjMaterialName,
+    ObjTextureTransform, UpAxis, Validation,
+};
 
 #[derive(Error, Debug)]
 pub enum ObjError {
     #[error("invalid obj format")]
     InvalidObjFormat,
+    #[error("missing material library {0}")]
+    MissingMaterialFile(String),
+    #[error("failed to parse obj: {0}")]
+    ObjParse(#[from] tobj::LoadError),
+    #[error("failed to parse material library {path}: {source}")]
+    MtlParse {
+        path: String,
+        #[source]
+        source: tobj::LoadError,
+    },
+    #[error("malformed vertex data in {mesh}: {detail}")]
+    MalformedVertexData { mesh: String, detail: String },
+    #[error("invalid model {model}: {reason}")]
+    Validation { model: String, reason: String },
+    #[error("failed to load texture {path}")]
+    TextureLoad {
+        path: String,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("failed to decode texture {path}")]
+    TextureDecode {
+        path: String,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("texture not found: {0}")]
+    TextureNotFound(String),
+    #[error("{0} leads out of the asset folder")]
+    InvalidPath(String),
+    #[error("failed to decompress obj: {0}")]
+    Decompress(std::io::Error),
+    #[error("combined mesh has more vertices than fit in a u32 index buffer")]
+    TooManyVertices,
+}
+
+// Color textures are authored in sRGB, while normal and other data maps must be sampled linearly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TextureKind {
+    Color,
+    Linear,
+    Normal,
+}
+
+impl TextureKind {
+    fn format(self) -> TextureFormat {
+        match self {
+            TextureKind::Color => TextureFormat::Rgba8UnormSrgb,
+            TextureKind::Linear | TextureKind::Normal => TextureFormat::Rgba8Unorm,
+        }
+    }
+
+    // The same image can be used as color in one material and as data in another, so the
+    // linear variant gets its own label and both can be loaded side by side.
+    fn label_suffix(self) -> &'static str {
+        match self {
+            TextureKind::Color => "",
+            TextureKind::Linear | TextureKind::Normal => "@linear",
+        }
+    }
+}
+
+// Settings for the files whose asset path matches a pattern, checked in order.
+pub(crate) type PathSettings = Vec<(String, ObjLoaderSettings)>;
+
+fn settings_for<'a>(
+    settings: &'a ObjLoaderSettings,
+    path_settings: &'a PathSettings,
+    path: &Path,
+) -> &'a ObjLoaderSettings {
+    let path = path.to_string_lossy().replace('\\', "/");
+    path_settings
+        .iter()
+        .find(|(pattern, _)| glob_match(pattern, &path))
+        .map_or(settings, |(_, settings)| settings)
 }
 
 #[derive(Default)]
-pub struct ObjLoader;
+pub struct ObjLoader<C = StandardMaterialConverter> {
+    settings: ObjLoaderSettings,
+    path_settings: PathSettings,
+    converter: Arc<C>,
+}
 
-impl AssetLoader for ObjLoader {
+impl ObjLoader {
+    pub fn new(settings: ObjLoaderSettings, path_settings: PathSettings) -> Self {
+        Self::with_converter(settings, path_settings, Arc::new(StandardMaterialConverter))
+    }
+}
+
+impl<C: MaterialConverter> ObjLoader<C> {
+    pub fn with_converter(
+        settings: ObjLoaderSettings,
+        path_settings: PathSettings,
+        converter: Arc<C>,
+    ) -> Self {
+        ObjLoader {
+            settings,
+            path_settings,
+            converter,
+        }
+    }
+}
+
+impl<C: MaterialConverter> AssetLoader for ObjLoader<C> {
     fn load<'a>(
         &'a self,
         bytes: &'a [u8],
         load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<()>> {
-        Box::pin(async move { Ok(load_obj(bytes, load_context).await?) })
+        Box::pin(async move {
+            let bytes = decompress(bytes)?;
+            let settings = settings_for(&self.settings, &self.path_settings, load_context.path());
+            Ok(load_obj(&bytes, load_context, settings, &*self.converter).await?)
+        })
     }
 
     fn extensions(&self) -> &[&str] {
-        &["obj"]
+        &["obj", "obj.gz", "objz"]
     }
 }
 
-struct Builder;
+/// Loads a `.mtl` material library on its own, as a `MtlLib` with every material also available
+/// under its name.
+#[derive(Default)]
+pub struct MtlLoader {
+    settings: ObjLoaderSettings,
+    path_settings: PathSettings,
+}
+
+impl MtlLoader {
+    pub fn new(settings: ObjLoaderSettings, path_settings: PathSettings) -> Self {
+        MtlLoader {
+            settings,
+            path_settings,
+        }
+    }
+}
+
+impl AssetLoader for MtlLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let settings = settings_for(&self.settings, &self.path_settings, load_context.path());
+            Ok(load_mtl(bytes, load_context, settings).await?)
+        })
+    }
 
-impl Builder {
-    pub fn build(&mut self, path: &Path) -> MTLLoadResult {
-        Err(LoadError::ReadError)
+    fn extensions(&self) -> &[&str] {
+        &["mtl", "mtl.gz"]
     }
 }
 
-async fn load_obj<'a, 'b>(
+async fn load_mtl<'a, 'b>(
     bytes: &'a [u8],
     load_context: &'a mut LoadContext<'b>,
+    settings: &'a ObjLoaderSettings,
 ) -> Result<(), ObjError> {
-    // For now do two passes:
-    // 1.  fetch all required materials
-    // 2.  load required materials
-    // 3.  reprocess the obj file
+    let (materials, _) = parse_material_lib(bytes, load_context.path())?;
 
-    let mut pending_materials = get_material_lib_paths(&mut BufReader::new(bytes))
-        .map_err(|e| ObjError::InvalidObjFormat)?;
+    load_textures(&materials, load_context, settings).await?;
+    let mut loaded_materials = Vec::with_capacity(materials.len());
+    for material in &materials {
+        let converted = convert_material(
+            &StandardMaterialConverter,
+            Some(material),
+            &material.name,
+            true,
+            false,
+            load_context,
+            settings,
+        );
+        loaded_materials.extend(converted);
+    }
+    load_context.set_default_asset(LoadedAsset::new(super::MtlLib {
+        materials: loaded_materials,
+    }));
+    Ok(())
+}
 
-    let mut materials = HashMap::new();
-    let parent = load_context.path().parent().unwrap();
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
 
-    for material in &pending_materials {
-        let bytes_vec = load_context
-            .read_asset_bytes(parent.join(material))
-            .await
-            .unwrap();
-        materials.insert(
-            material.clone(),
-            tobj::load_mtl_buf(&mut BufReader::new(bytes_vec.as_slice())),
-        );
+fn decompress(bytes: &[u8]) -> Result<Cow<'_, [u8]>, ObjError> {
+    if bytes.starts_with(&GZIP_MAGIC) {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(bytes)
+            .read_to_end(&mut decompressed)
+            .map_err(ObjError::Decompress)?;
+        Ok(Cow::Owned(decompressed))
+    } else {
+        Ok(Cow::Borrowed(bytes))
+    }
+}
+
+fn compressed_path(path: &Path) -> PathBuf {
+    let mut compressed = path.as_os_str().to_owned();
+    compressed.push(".gz");
+    PathBuf::from(compressed)
+}
+
+async fn load_obj<'a, 'b, C: MaterialConverter>(
+    bytes: &'a [u8],
+    load_context: &'a mut LoadContext<'b>,
+    settings: &'a ObjLoaderSettings,
+    converter: &'a C,
+) -> Result<(), ObjError> {
+    let path = load_context.path().to_path_buf();
+    let prepared = prepare_obj(bytes, &path, settings)?;
+    // Parsing can't wait for the asset server, so the material libraries are all read up front.
+    let mut libraries = HashMap::new();
+    for library in prepared.material_lib_candidates(asset_dir(&path), settings) {
+        let bytes = load_context.read_asset_bytes(&library).await;
+        if bytes.is_err() {
+            let compressed = compressed_path(&library);
+            let bytes = load_context.read_asset_bytes(&compressed).await;
+            libraries.insert(compressed, bytes);
+        }
+        libraries.insert(library, bytes);
     }
+    let parsed = parse_prepared(prepared, &path, settings, |library| {
+        libraries.get(library)?.as_ref().ok().cloned()
+    })?;
+    register_obj(parsed, load_context, settings, converter).await
+}
 
-    let (meshes, materials) = tobj::load_obj_buf(&mut BufReader::new(bytes), false, |p| {
-        if let Some(res) = materials.get(&p.to_str().unwrap().to_string()) {
-            res.clone()
-        } else {
-            Err(LoadError::ReadError)
+/// An OBJ file parsed into meshes by `parse_obj`, with its materials not yet converted and their
+/// textures not yet loaded.
+pub struct ParsedObj {
+    pub meshes: Vec<ParsedMesh>,
+    /// Every material of the material libraries that were found, in the order they are defined.
+    pub materials: Vec<tobj::Material>,
+    /// The material library each of `materials` comes from, as named by `mtllib`.
+    pub material_libs: Vec<String>,
+    /// Indices into `materials` in the order the file first uses them, followed by the unused
+    /// ones.
+    pub material_order: Vec<usize>,
+    /// Paths of the material libraries that were read.
+    pub material_lib_paths: Vec<PathBuf>,
+    pub has_vertex_colors: bool,
+    /// Whether the file gives normals, rather than leaving them to be generated.
+    pub has_normals: bool,
+    /// Whether the file gives texture coordinates, rather than leaving them zeroed.
+    pub has_texcoords: bool,
+    /// Bounds of every vertex in the file, collapsed to the origin when there are none.
+    pub aabb_min: Vec3,
+    pub aabb_max: Vec3,
+}
+
+/// A mesh of a `ParsedObj`.
+pub struct ParsedMesh {
+    /// The name of the mesh's object, made unique within the file. The loader uses it as the
+    /// mesh's label.
+    pub name: String,
+    pub mesh: Mesh,
+    /// Index into `ParsedObj::materials`.
+    pub material: Option<usize>,
+    /// The name the mesh's `usemtl` asked for when no material library defines it, in which case
+    /// `material` is `None`.
+    pub missing_material: Option<String>,
+    pub texture_transform: Option<ObjTextureTransform>,
+    /// Whether the object is excluded from the scene by the include and exclude patterns.
+    pub excluded: bool,
+    /// Bounds of the mesh's vertices, collapsed to the origin when there are none.
+    pub aabb_min: Vec3,
+    pub aabb_max: Vec3,
+}
+
+/// Parses an OBJ file, which may be gzip compressed, without an asset server. `path` is where the
+/// file is, used to resolve material libraries and in warnings. `read_material_lib` is given the
+/// path of every material library the file references and returns its contents, or `None` when
+/// it doesn't exist.
+pub fn parse_obj(
+    bytes: &[u8],
+    path: &Path,
+    read_material_lib: impl Fn(&Path) -> Option<Vec<u8>>,
+    settings: &ObjLoaderSettings,
+) -> Result<ParsedObj, ObjError> {
+    let bytes = decompress(bytes)?;
+    let prepared = prepare_obj(&bytes, path, settings)?;
+    parse_prepared(prepared, path, settings, read_material_lib)
+}
+
+/// Parses OBJ data that doesn't come from a file of the asset server, like a download. Material
+/// libraries are read from the file system relative to `base_path`, and skipped when it is
+/// `None`. Texture paths are left in the materials for the caller to resolve against the same
+/// directory.
+///
+/// The libraries are read with `std::fs`, which blocks until they are, so call it from a thread
+/// that may block, like one of `AsyncComputeTaskPool`, rather than from a system. Use `parse_obj`
+/// to read them some other way.
+pub fn load_obj_from_bytes_blocking(
+    bytes: &[u8],
+    base_path: Option<&Path>,
+    settings: &ObjLoaderSettings,
+) -> Result<ParsedObj, ObjError> {
+    // Libraries are resolved against the directory of the given path, which has none here.
+    let path = Path::new("<memory>");
+    match base_path {
+        Some(base_path) => parse_obj(
+            bytes,
+            path,
+            |library| std::fs::read(base_path.join(library)).ok(),
+            settings,
+        ),
+        None => {
+            let settings = ObjLoaderSettings {
+                meshes_only: true,
+                ..settings.clone()
+            };
+            parse_obj(bytes, path, |_| None, &settings)
         }
+    }
+}
+
+// An OBJ file rewritten into something tobj parses the way it's meant to be read.
+struct PreparedObj<'a> {
+    bytes: Cow<'a, [u8]>,
+    scan: ObjScan,
+    indexed_vertices: Option<IndexedVertices>,
+    transform: ImportTransform,
+    use_smoothing_groups: bool,
+}
+
+impl PreparedObj<'_> {
+    // Paths that may be read as material libraries, see `read_material_libs`.
+    fn material_lib_candidates(&self, parent: &Path, settings: &ObjLoaderSettings) -> Vec<PathBuf> {
+        let mut candidates = Vec::new();
+        if settings.meshes_only {
+            return candidates;
+        }
+        for mtllib in &self.scan.material_libs {
+            candidates.extend(normalize_asset_path(parent, mtllib).ok());
+            if mtllib.contains(char::is_whitespace) {
+                candidates.extend(
+                    mtllib
+                        .split_whitespace()
+                        .filter_map(|word| normalize_asset_path(parent, word).ok()),
+                );
+            }
+        }
+        candidates
+    }
+}
+
+fn prepare_obj<'a>(
+    bytes: &'a [u8],
+    path: &Path,
+    settings: &ObjLoaderSettings,
+) -> Result<PreparedObj<'a>, ObjError> {
+    let transform = ImportTransform::new(settings);
+    let scan = scan_obj(&mut BufReader::new(bytes)).map_err(|_| ObjError::InvalidObjFormat)?;
+    let mut bytes = Cow::Borrowed(bytes);
+    if scan.has_suspect_faces {
+        if let Some(valid) = validate_faces(&bytes, &scan, settings.validation, path)? {
+            bytes = Cow::Owned(valid);
+        }
+    }
+    if scan.needs_element_rewrite {
+        bytes = Cow::Owned(rewrite_elements(&bytes));
+    }
+    // Normals given by the file already encode its hard edges, so smoothing groups only matter
+    // when smooth normals are generated.
+    let use_smoothing_groups = scan.has_smoothing_groups
+        && !scan.has_normals
+        && settings.generated_normals == GeneratedNormals::Smooth;
+    if use_smoothing_groups {
+        bytes = Cow::Owned(split_smoothing_groups(&bytes));
+    }
+    if settings.triangulate && scan.polygon_faces > 0 {
+        let (clipped, concave_faces) = clip_concave_faces(&bytes);
+        if concave_faces > 0 {
+            debug!(
+                "{}: ear clipped {} concave faces",
+                path.display(),
+                concave_faces
+            );
+            bytes = Cow::Owned(clipped);
+        }
+    }
+    // Files made of nothing but vertices are point clouds.
+    if scan.vertex_count > 0 && !scan.has_elements {
+        let mut points = bytes.into_owned();
+        points.push(b'\n');
+        for vertex in 1..=scan.vertex_count {
+            points.extend_from_slice(format!("l {} {}\n", vertex, vertex).as_bytes());
+        }
+        bytes = Cow::Owned(points);
+    }
+    // Comes after ear clipping, which needs the real positions.
+    let indexed_vertices = if scan.has_vertex_colors {
+        let (indexed, vertices) = index_vertices(&bytes);
+        bytes = Cow::Owned(indexed);
+        Some(vertices)
+    } else {
+        None
+    };
+    // tobj resolves `usemtl` only against the libraries it has seen so far, which would leave
+    // materials used before the `mtllib` statement unresolved. Every library is handed over on
+    // the first request anyway, so it's enough to request one at the very start.
+    if scan.usemtl_before_mtllib {
+        if let Some(mtllib) = scan.material_libs.first() {
+            let mut prefixed = format!("mtllib {}\n", mtllib).into_bytes();
+            prefixed.extend_from_slice(&bytes);
+            bytes = Cow::Owned(prefixed);
+        }
+    }
+    Ok(PreparedObj {
+        bytes,
+        scan,
+        indexed_vertices,
+        transform,
+        use_smoothing_groups,
     })
-    .unwrap();
+}
 
-    let mut loaded_materials = Vec::with_capacity(materials.len());
-    for material in materials {
-        loaded_materials.push(
-            load_material(&material, load_context)
-                .await
-                .map_err(|e| ObjError::InvalidObjFormat)?,
-        );
+// Reads every material library of the file, returning each with the name it was found under and
+// its path, along with whether none were skipped. Libraries that are missing or fail to parse are
+// skipped, the latter only unless `ObjLoaderSettings::strict_materials` is set.
+fn read_material_libs(
+    material_libs: &[String],
+    path: &Path,
+    read_material_lib: impl Fn(&Path) -> Option<Vec<u8>>,
+    settings: &ObjLoaderSettings,
+) -> Result<(Vec<FoundMaterialLib>, bool), ObjError> {
+    let parent = asset_dir(path);
+    let mut libraries = Vec::with_capacity(material_libs.len());
+    let mut complete = true;
+    for mtllib in material_libs {
+        // Libraries may have been compressed without updating the `mtllib` statement.
+        let read = |name: &str| {
+            let material_path = normalize_asset_path(parent, name)?;
+            Ok(match read_material_lib(&material_path) {
+                Some(bytes) => (name.to_string(), material_path, Some(bytes)),
+                None => {
+                    let compressed = compressed_path(&material_path);
+                    let bytes = read_material_lib(&compressed);
+                    (name.to_string(), compressed, bytes)
+                }
+            })
+        };
+        let mut candidates: Vec<Result<_, ObjError>> = vec![read(mtllib)];
+        // The spec allows both spaces in a library name and several libraries on one line, so
+        // when no library has the whole name each word is tried as a library of its own.
+        let found = matches!(candidates[0], Ok((_, _, Some(_))));
+        if !found && mtllib.contains(char::is_whitespace) {
+            candidates = mtllib.split_whitespace().map(read).collect();
+        }
+        for candidate in candidates {
+            let (name, material_path, bytes) = match candidate {
+                Ok(candidate) => candidate,
+                Err(e) => {
+                    warn!("{}: skipping material library, {}", path.display(), e);
+                    complete = false;
+                    continue;
+                }
+            };
+            match bytes {
+                Some(bytes) => match parse_material_lib(&bytes, &material_path) {
+                    Ok(lib) => libraries.push((name, material_path, lib)),
+                    Err(e) if settings.strict_materials => return Err(e),
+                    Err(e) => {
+                        warn!("{}: skipping material library, {}", path.display(), e);
+                        complete = false;
+                    }
+                },
+                None => {
+                    warn!(
+                        "{}: skipping missing material library {}",
+                        path.display(),
+                        name
+                    );
+                    complete = false;
+                }
+            }
+        }
     }
+    Ok((libraries, complete))
+}
 
-    let mut loaded_meshes = Vec::with_capacity(meshes.len());
+fn parse_material_lib(bytes: &[u8], material_path: &Path) -> Result<MaterialLib, ObjError> {
+    let bytes = decompress(bytes)?;
+    tobj::load_mtl_buf(&mut BufReader::new(&*bytes)).map_err(|source| ObjError::MtlParse {
+        path: material_path.display().to_string(),
+        source,
+    })
+}
 
-    let mut world = World::default();
+fn parse_prepared(
+    prepared: PreparedObj,
+    path: &Path,
+    settings: &ObjLoaderSettings,
+    read_material_lib: impl Fn(&Path) -> Option<Vec<u8>>,
+) -> Result<ParsedObj, ObjError> {
+    let PreparedObj {
+        bytes,
+        scan,
+        indexed_vertices,
+        transform,
+        use_smoothing_groups,
+    } = prepared;
 
-    world
-        .spawn()
-        .insert_bundle((Transform::identity(), GlobalTransform::identity()))
-        .with_children(|parent| {
-            for (i, tobj_mesh) in meshes.into_iter().enumerate() {
-                let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-                mesh.set_attribute(
-                    Mesh::ATTRIBUTE_POSITION,
-                    VertexAttributeValues::Float3(
-                        chunk_by::<f32, 3>(&tobj_mesh.mesh.positions).unwrap(),
-                    ),
-                );
+    // Without material libraries every mesh ends up without a material.
+    let material_lib_names: &[String] = if settings.meshes_only {
+        &[]
+    } else {
+        &scan.material_libs
+    };
+    let mut material_libs = Vec::with_capacity(material_lib_names.len());
+    let mut material_lib_paths = Vec::with_capacity(material_lib_names.len());
+    let (libraries, complete) =
+        read_material_libs(material_lib_names, path, read_material_lib, settings)?;
+    for (name, material_path, lib) in libraries {
+        material_libs.push((name, lib));
+        material_lib_paths.push(material_path);
+    }
 
-                mesh.set_attribute(
-                    Mesh::ATTRIBUTE_NORMAL,
-                    VertexAttributeValues::Float3(
-                        chunk_by::<f32, 3>(&tobj_mesh.mesh.normals).unwrap(),
-                    ),
+    // tobj asks for every `mtllib` separately, but the libraries were all fetched up front, so
+    // the merged set is handed over on the first request and later requests add nothing. This
+    // keeps `usemtl` resolution independent of how tobj spells the library path.
+    let (mut combined_materials, material_sources) = merge_material_libs(material_libs);
+    // tobj leaves meshes using a material no library defines without one, like meshes that never
+    // asked for one. Placeholders appended after the real materials tell the two apart. A
+    // skipped library may well have defined the material though, so then its meshes are left
+    // with the default material like those of a file shipped without its libraries.
+    let defined_materials = combined_materials.0.len();
+    if !settings.meshes_only && complete {
+        let (placeholders, names) = &mut combined_materials;
+        for name in &scan.material_names {
+            if !names.contains_key(name) {
+                warn!(
+                    "{}: material {} is not defined by any material library",
+                    path.display(),
+                    name
                 );
+                names.insert(name.clone(), placeholders.len());
+                placeholders.push(tobj::Material {
+                    name: name.clone(),
+                    ..tobj::Material::empty()
+                });
+            }
+        }
+    }
+    let provided = Cell::new(false);
+    let (models, mut materials) =
+        tobj::load_obj_buf(&mut BufReader::new(&*bytes), settings.triangulate, |_| {
+            if provided.replace(true) {
+                Ok((Vec::new(), HashMap::new()))
+            } else {
+                Ok(combined_materials.clone())
+            }
+        })?;
+    let mut meshes = models
+        .into_iter()
+        .map(|mut model| {
+            let colors = match &indexed_vertices {
+                Some(vertices) => restore_vertices(&mut model.mesh, vertices),
+                None => Vec::new(),
+            };
+            (model, colors)
+        })
+        .collect::<Vec<_>>();
+    if use_smoothing_groups {
+        for (model, _) in &mut meshes {
+            model.mesh.normals.clear();
+        }
+    }
+    if !transform.is_identity() {
+        for (model, _) in &mut meshes {
+            transform.apply(&mut model.mesh);
+        }
+    }
+    materials.truncate(defined_materials);
+    let material_order = material_order(&meshes, materials.len());
+    let meshes = match settings.mesh_merging {
+        MeshMerging::None => meshes,
+        merging => combine_models(meshes, merging)?,
+    };
+    let meshes = split_by_topology(meshes);
 
-                mesh.set_attribute(
-                    Mesh::ATTRIBUTE_UV_0,
-                    VertexAttributeValues::Float2(
-                        chunk_by::<f32, 2>(&tobj_mesh.mesh.texcoords).unwrap(),
-                    ),
+    let mut parsed_meshes = Vec::with_capacity(meshes.len());
+    // Objects named like one of the fixed labels are renamed rather than replacing that asset.
+    let mut used_labels = RESERVED_LABELS
+        .iter()
+        .map(|label| label.to_string())
+        .collect::<HashSet<_>>();
+    let mut bounds = None;
+
+    for (mut tobj_mesh, mut colors, topology) in meshes {
+        let name = model_label(&tobj_mesh, &materials);
+        validate_mesh(
+            &mut tobj_mesh.mesh,
+            &mut colors,
+            topology,
+            &name,
+            settings.validation,
+            path,
+        )?;
+        // Runs on the converted vertex data, as the axis conversion and scale can mirror it too.
+        if settings.fix_winding && topology == Topology::Triangles {
+            let fixed = fix_winding(&mut tobj_mesh.mesh);
+            if fixed > 0 {
+                debug!(
+                    "{}: turned {} faces of {} around",
+                    path.display(),
+                    fixed,
+                    name
                 );
+            }
+        }
+        let mut mesh_bounds = None;
+        extend_bounds(&mut mesh_bounds, &tobj_mesh.mesh.positions);
+        if let Some((min, max)) = mesh_bounds {
+            bounds = Some(match bounds {
+                Some((total_min, total_max)) => (min.min(total_min), max.max(total_max)),
+                None => (min, max),
+            });
+        }
+        let (aabb_min, aabb_max) = mesh_bounds.unwrap_or((Vec3::ZERO, Vec3::ZERO));
 
-                mesh.set_indices(Some(Indices::U32(tobj_mesh.mesh.indices)));
-
-                let mesh = load_context.set_labeled_asset(&tobj_mesh.name, LoadedAsset::new(mesh));
-                let material = tobj_mesh
-                    .mesh
-                    .material_id
-                    .and_then(|i| loaded_materials.get(i).cloned());
-                let loaded_mesh = load_context.set_labeled_asset(
-                    &format!("ObjMesh{}", i),
-                    LoadedAsset::new(super::ObjMesh {
-                        mesh: mesh.clone(),
-                        material: material.clone(),
-                    }),
+        let missing_material = tobj_mesh
+            .mesh
+            .material_id
+            .filter(|&i| i >= defined_materials)
+            .map(|i| combined_materials.0[i].name.clone());
+        // Normal maps need tangents, which are only worth computing for the meshes using them.
+        let material = tobj_mesh.mesh.material_id.filter(|&i| i < materials.len());
+        let has_normal_map = material.is_some_and(|i| !materials[i].normal_texture.is_empty());
+        // Missing texcoords are zeroed, which samples a single texel of any texture.
+        if tobj_mesh.mesh.texcoords.is_empty() && topology == Topology::Triangles {
+            let textured = material.and_then(|i| {
+                TextureSlot::ALL
+                    .iter()
+                    .find_map(|slot| slot.texture(&materials[i]))
+            });
+            if textured.is_some() {
+                warn!(
+                    "{}: {} has no texture coordinates for the textures of its material",
+                    path.display(),
+                    name
                 );
+            }
+        }
+        let mut mesh = build_mesh(
+            &tobj_mesh.mesh,
+            &name,
+            topology,
+            has_normal_map,
+            &colors,
+            settings,
+        )?;
+        let mut texture_transform = material.and_then(|i| texture_transform(&materials[i]));
+        if settings.bake_texture_transform {
+            if let Some(transform) = texture_transform.take() {
+                bake_texture_transform(&mut mesh, transform, settings.flip_uv_v);
+            }
+        }
+        parsed_meshes.push(ParsedMesh {
+            name: unique_label(&name, &mut used_labels),
+            mesh,
+            material,
+            missing_material,
+            texture_transform,
+            excluded: is_excluded(&tobj_mesh.name, settings),
+            aabb_min,
+            aabb_max,
+        });
+    }
+
+    let (aabb_min, aabb_max) = bounds.unwrap_or((Vec3::ZERO, Vec3::ZERO));
+    Ok(ParsedObj {
+        meshes: parsed_meshes,
+        materials,
+        material_libs: material_sources,
+        material_order,
+        material_lib_paths,
+        has_vertex_colors: scan.has_vertex_colors,
+        has_normals: scan.has_normals,
+        has_texcoords: scan.has_texcoords,
+        aabb_min,
+        aabb_max,
+    })
+}
+
+// Loads the textures and materials of a parsed file and registers it all with the load context.
+async fn register_obj<'a, 'b, C: MaterialConverter>(
+    parsed: ParsedObj,
+    load_context: &'a mut LoadContext<'b>,
+    settings: &'a ObjLoaderSettings,
+    converter: &'a C,
+) -> Result<(), ObjError> {
+    let ParsedObj {
+        meshes,
+        materials,
+        material_libs: material_sources,
+        material_order,
+        material_lib_paths,
+        has_vertex_colors,
+        has_normals,
+        has_texcoords,
+        aabb_min,
+        aabb_max,
+    } = parsed;
+    // Material libraries and textures are registered as dependencies so that changes to them are
+    // picked up by the asset watcher, not just changes to the obj itself.
+    let dependencies = material_lib_paths
+        .into_iter()
+        .map(|path| AssetPath::new(path, None))
+        .collect::<Vec<_>>();
 
-                let bundle = if let Some(material) = material {
-                    PbrBundle {
-                        mesh,
+    let textures = load_textures(&materials, load_context, settings).await?;
+    let mut converted_materials = HashMap::new();
+    // Surfaces use the converted MTL material, while lines and points get a conversion of their
+    // own. Meshes without a material use one of the fallbacks.
+    let mut material_for =
+        |material_id: Option<usize>, surface: bool, load_context: &mut LoadContext| {
+            converted_materials
+                .entry((material_id, surface))
+                .or_insert_with(|| {
+                    let label = match material_id {
+                        Some(i) if surface => material_label(&material_sources[i], &materials[i]),
+                        Some(i) => format!(
+                            "{}/Unlit",
+                            material_label(&material_sources[i], &materials[i])
+                        ),
+                        None if !surface => "UnlitMaterial".to_string(),
+                        None if has_vertex_colors && settings.default_material.is_none() => {
+                            "VertexColorMaterial".to_string()
+                        }
+                        None => "DefaultMaterial".to_string(),
+                    };
+                    let material = material_id.map(|i| &materials[i]);
+                    convert_material(
+                        converter,
                         material,
-                        ..Default::default()
-                    }
-                } else {
-                    PbrBundle {
-                        mesh,
-                        ..Default::default()
-                    }
-                };
+                        &label,
+                        surface,
+                        has_vertex_colors,
+                        load_context,
+                        settings,
+                    )
+                })
+                .clone()
+        };
+    let mut loaded_materials = Vec::with_capacity(materials.len());
+    for i in 0..materials.len() {
+        loaded_materials.push(material_for(Some(i), true, load_context));
+    }
 
-                parent.spawn_bundle(bundle);
+    let mut loaded_meshes = Vec::with_capacity(meshes.len());
+    let mut excluded_meshes = Vec::new();
+    let mut bundles = Vec::with_capacity(meshes.len());
+    let single_model = meshes.len() == 1;
+    let mut mesh_vertex_counts = Vec::with_capacity(meshes.len());
+    let mut triangle_count = 0;
+    let mut instances = HashMap::new();
+    let mut instance_count = 0;
+    let mut missing_materials = HashMap::new();
 
-                loaded_meshes.push(loaded_mesh);
+    for (i, parsed_mesh) in meshes.into_iter().enumerate() {
+        let mesh = parsed_mesh.mesh;
+        let surface = mesh.primitive_topology() == PrimitiveTopology::TriangleList;
+        mesh_vertex_counts.push(mesh.count_vertices());
+        if surface {
+            triangle_count += match mesh.indices() {
+                Some(Indices::U16(indices)) => indices.len() / 3,
+                Some(Indices::U32(indices)) => indices.len() / 3,
+                None => mesh.count_vertices() / 3,
+            };
+        }
+        let label = parsed_mesh.name;
+        let instance_key = settings.instance_meshes.then(|| instance_key(&mesh));
+        let instance = instance_key
+            .as_ref()
+            .and_then(|key| instances.get(key))
+            .cloned();
+        // Object names vary between exporters, so every mesh is also reachable by its position in
+        // the file, copies shared through instancing included, and a file with a single model
+        // exposes it as the default asset.
+        if settings.mesh_index_labels {
+            load_context.set_labeled_asset(&format!("Mesh{}", i), LoadedAsset::new(mesh.clone()));
+            if single_model {
+                load_context.set_default_asset(LoadedAsset::new(mesh.clone()));
+            }
+        }
+        let mesh = match instance {
+            Some(instance) => {
+                instance_count += 1;
+                instance
+            }
+            None => {
+                let mesh = load_context.set_labeled_asset(&label, LoadedAsset::new(mesh));
+                if let Some(key) = instance_key {
+                    instances.insert(key, mesh.clone());
+                }
+                mesh
             }
+        };
+        let material_id = parsed_mesh.material;
+        let material_name =
+            material_id.map(|i| material_label(&material_sources[i], &materials[i]));
+        // Lines and points can't be shaded like the rest of the model, so even those without a
+        // material get one of their own.
+        let fallback_only = surface && material_id.is_none() && settings.default_material.is_none();
+        let material = if settings.meshes_only {
+            None
+        } else if parsed_mesh.missing_material.is_some() {
+            missing_materials
+                .entry(surface)
+                .or_insert_with(|| {
+                    let label = if surface {
+                        "MissingMaterial"
+                    } else {
+                        "MissingMaterial/Unlit"
+                    };
+                    convert_material(
+                        converter,
+                        Some(&missing_material()),
+                        label,
+                        surface,
+                        has_vertex_colors,
+                        load_context,
+                        settings,
+                    )
+                })
+                .clone()
+        } else if fallback_only {
+            None
+        } else {
+            material_for(material_id, surface, load_context)
+        };
+        let loaded_mesh = load_context.set_labeled_asset(
+            &format!("ObjMesh/{}", label),
+            LoadedAsset::new(super::ObjMesh {
+                name: label.clone(),
+                mesh: mesh.clone(),
+                material_name: material_name.clone(),
+                material: material.clone(),
+                ambient_color: material_id.and_then(|i| ambient_color(&materials[i])),
+                texture_transform: parsed_mesh.texture_transform,
+                aabb_min: parsed_mesh.aabb_min,
+                aabb_max: parsed_mesh.aabb_max,
+            }),
+        );
+        let excluded = parsed_mesh.excluded;
+        if excluded {
+            excluded_meshes.push(loaded_mesh.clone());
+        }
+        loaded_meshes.push(loaded_mesh);
+        if settings.meshes_only || (excluded && settings.excluded_meshes == ExcludedMeshes::Skip) {
+            continue;
+        }
+
+        let material = material.or_else(|| material_for(None, surface, load_context));
+        let source = material_id.map(|i| &materials[i]);
+        let context = MaterialContext {
+            load_context,
+            settings,
+            surface,
+            has_vertex_colors,
+        };
+        bundles.push(SceneMesh {
+            bundle: material
+                .map(|material| converter.bundle(mesh.clone(), material, source, &context)),
+            mesh,
+            texture_transform: parsed_mesh.texture_transform,
+            material_name: material_name.map(ObjMaterialName),
+            name: Name::new(label),
+            hidden: excluded,
         });
+    }
+
+    if instance_count > 0 {
+        debug!(
+            "{}: {} meshes share the geometry of an earlier one",
+            load_context.path().display(),
+            instance_count
+        );
+    }
+
+    let materials = material_order
+        .iter()
+        .filter_map(|&i| loaded_materials[i].clone())
+        .collect::<Vec<_>>();
+    let stats = load_context.set_labeled_asset(
+        "Stats",
+        LoadedAsset::new(super::ObjLoadStats {
+            object_count: loaded_meshes.len(),
+            vertex_count: mesh_vertex_counts.iter().sum(),
+            triangle_count,
+            material_count: materials.len(),
+            texture_count: textures.count,
+            texture_memory: textures.memory,
+            has_normals,
+            has_texcoords,
+            mesh_vertex_counts,
+        }),
+    );
+    let scene = if settings.meshes_only {
+        None
+    } else {
+        let path = AssetPath::new_ref(load_context.path(), Some("Scene"));
+        Some(load_context.get_handle(path))
+    };
     load_context.set_labeled_asset(
         "Obj",
         LoadedAsset::new(super::Obj {
-            materials: loaded_materials,
+            materials,
             meshes: loaded_meshes,
-        }),
+            excluded_meshes,
+            scene,
+            stats,
+            aabb_min,
+            aabb_max,
+        })
+        .with_dependencies(dependencies),
     );
+    if settings.meshes_only {
+        return Ok(());
+    }
 
+    let mut world = World::default();
+    let root = root_transform(settings);
+
+    world
+        .spawn()
+        .insert_bundle((
+            root,
+            GlobalTransform::from(root),
+            Name::new(scene_name(load_context.path())),
+        ))
+        .with_children(|parent| {
+            for scene_mesh in bundles {
+                let mut entity = match scene_mesh.bundle {
+                    Some(bundle) => parent.spawn_bundle(bundle),
+                    // Meshes the converter gave no material are still placed in the scene.
+                    None => parent.spawn_bundle((
+                        scene_mesh.mesh,
+                        Transform::identity(),
+                        GlobalTransform::identity(),
+                    )),
+                };
+                entity.insert(scene_mesh.name);
+                if let Some(texture_transform) = scene_mesh.texture_transform {
+                    entity.insert(texture_transform);
+                }
+                if let Some(material_name) = scene_mesh.material_name {
+                    entity.insert(material_name);
+                }
+                if scene_mesh.hidden {
+                    entity.insert(Visible {
+                        is_visible: false,
+                        is_transparent: false,
+                    });
+                }
+            }
+        });
     load_context.set_labeled_asset("Scene", LoadedAsset::new(Scene::new(world)));
 
     Ok(())
 }
 
-async fn load_material<'a, 'b>(
-    material: &tobj::Material,
-    load_context: &'a mut LoadContext<'b>,
-) -> Result<Handle<StandardMaterial>> {
-    let material_label = material_label(material);
+// A mesh entity of the scene, spawned once all meshes have been loaded.
+struct SceneMesh<B> {
+    bundle: Option<B>,
+    mesh: Handle<Mesh>,
+    texture_transform: Option<ObjTextureTransform>,
+    material_name: Option<ObjMaterialName>,
+    name: Name,
+    hidden: bool,
+}
 
-    let base_color_texture = try_texture_handle(&material.diffuse_texture, load_context).await?;
+// Objects are excluded when they match an exclude pattern, or when include patterns are given
+// and they match none of them.
+fn is_excluded(name: &str, settings: &ObjLoaderSettings) -> bool {
+    let matches = |patterns: &[String]| patterns.iter().any(|pattern| glob_match(pattern, name));
+    matches(&settings.exclude) || (!settings.include.is_empty() && !matches(&settings.include))
+}
 
-    let normal_map = try_texture_handle(&material.normal_texture, load_context).await?;
+// Matches `*` against any run of characters; everything else must match exactly.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let mut rest = match name.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts = parts.collect::<Vec<_>>();
+    let (last, middle) = match parts.split_last() {
+        Some(split) => split,
+        // No `*` at all, so the whole name must have been the prefix.
+        None => return rest.is_empty(),
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
 
-    let metallic_roughness_texture =
-        try_texture_handle(&material.specular_texture, load_context).await?;
+// Converts a material under `label`, unless a material of the same label was converted before.
+fn convert_material<C: MaterialConverter>(
+    converter: &C,
+    material: Option<&tobj::Material>,
+    label: &str,
+    surface: bool,
+    has_vertex_colors: bool,
+    load_context: &mut LoadContext,
+    settings: &ObjLoaderSettings,
+) -> Option<Handle<C::Material>> {
+    // A library defining the same material twice yields two identical entries.
+    if load_context.has_labeled_asset(label) {
+        let path = AssetPath::new_ref(load_context.path(), Some(label));
+        return Some(load_context.get_handle(path));
+    }
+    // The material's textures must already have been loaded by `load_textures`.
+    let context = MaterialContext {
+        load_context,
+        settings,
+        surface,
+        has_vertex_colors,
+    };
+    let material = converter.convert(material, &context)?;
+    Some(load_context.set_labeled_asset(label, LoadedAsset::new(material)))
+}
 
-    let occlusion_texture = try_texture_handle(&material.ambient_texture, load_context).await?;
+fn ambient_color(material: &tobj::Material) -> Option<Color> {
+    let [r, g, b] = material.ambient;
+    if r > 0.0 || g > 0.0 || b > 0.0 {
+        Some(Color::rgb(r, g, b))
+    } else {
+        None
+    }
+}
 
-    Ok(load_context.set_labeled_asset(
-        &material_label,
-        LoadedAsset::new(StandardMaterial {
-            base_color: Color::rgb(
-                material.diffuse[0],
-                material.diffuse[1],
-                material.diffuse[2],
-            ),
-            base_color_texture,
-            metallic_roughness_texture,
-            reflectance: material.shininess,
-            normal_map,
-            occlusion_texture,
-            ..Default::default()
-        }),
-    ))
-}
-
-fn chunk_by<'a, T: 'a + Clone, const N: usize>(v: &'a [T]) -> Result<Vec<[T; N]>>
-where
-    [T; N]: TryFrom<&'a [T]>,
-{
-    v.chunks(N)
-        .map(|x| {
-            x.clone()
-                .try_into()
-                .map_err(|e| anyhow::Error::msg("failed to chunk"))
-        })
-        .collect()
+/// Converts an MTL `Ns` Blinn-Phong specular exponent into Bevy's perceptual roughness.
+/// `sqrt(2 / (Ns + 2))` gives the Beckmann roughness with a matching highlight, which Bevy's
+/// perceptual roughness is the square root of, so the result is `(2 / (Ns + 2))^0.25`. `Ns` is
+/// clamped to the [0, 1000] of the MTL spec, so `Ns = 0` is fully rough and `Ns = 1000` the
+/// glossiest at about 0.21.
+pub fn shininess_to_roughness(shininess: f32) -> f32 {
+    (2.0 / (shininess.clamp(0.0, 1000.0) + 2.0)).sqrt().sqrt()
 }
 
-async fn try_texture_handle<'a, 'b>(
-    texture: &String,
-    load_context: &'a mut LoadContext<'b>,
-) -> Result<Option<Handle<Texture>>> {
-    if !texture.is_empty() {
-        let label = texture_label(&texture);
-        load_texture(texture, load_context).await?;
-        let path = AssetPath::new_ref(load_context.path(), Some(&label));
+// Meshes using a material no library defines get a bright magenta one, so that the mistake shows
+// rather than the mesh quietly rendering in the default color.
+fn missing_material() -> tobj::Material {
+    tobj::Material {
+        name: "MissingMaterial".to_string(),
+        diffuse: [1.0, 0.0, 1.0],
+        ..tobj::Material::empty()
+    }
+}
 
-        Ok(Some(load_context.get_handle(path)))
-    } else {
-        Ok(None)
+// Materials are listed in the order the file first uses them, followed by the unused ones in the
+// order they are defined.
+fn material_order(models: &[ColoredModel], material_count: usize) -> Vec<usize> {
+    let mut order = Vec::with_capacity(material_count);
+    let used = models
+        .iter()
+        .filter_map(|(model, _)| model.mesh.material_id);
+    for material_id in used.chain(0..material_count) {
+        if material_id < material_count && !order.contains(&material_id) {
+            order.push(material_id);
+        }
     }
+    order
 }
 
-async fn load_texture<'a, 'b>(
-    texture: &String,
-    load_context: &'a mut LoadContext<'b>,
-) -> Result<()> {
-    let label = texture_label(texture);
-    let parent = load_context.path().parent().unwrap();
-    let image_path = parent.join(texture);
-
-    let bytes = load_context.read_asset_bytes(image_path.clone()).await?;
-
-    let mut texture = Texture::from_buffer(
-        &bytes,
-        ImageType::Extension(image_path.extension().unwrap().to_str().unwrap()),
-    )?;
-    texture.sampler = texture_sampler();
-    texture.format = TextureFormat::Rgba8UnormSrgb;
-    load_context.set_labeled_asset(&label, LoadedAsset::new(texture));
-    Ok(())
+type MaterialLib = (Vec<tobj::Material>, HashMap<String, usize>);
+
+// A material library with the name it was found under and its path.
+type FoundMaterialLib = (String, PathBuf, MaterialLib);
+
+// Mirrors how tobj merges several libraries: materials are appended in order and a name defined
+// again by a later library points at the later definition. The library each material came from
+// is returned alongside, in the same order, to tell apart materials sharing a name.
+fn merge_material_libs(libs: Vec<(String, MaterialLib)>) -> (MaterialLib, Vec<String>) {
+    let mut materials = Vec::new();
+    let mut names = HashMap::new();
+    let mut sources = Vec::new();
+    for (lib, (mut lib_materials, lib_names)) in libs {
+        let offset = materials.len();
+        sources.resize(offset + lib_materials.len(), lib);
+        materials.append(&mut lib_materials);
+        for (name, index) in lib_names {
+            names.insert(name, index + offset);
+        }
+    }
+    ((materials, names), sources)
 }
 
-fn texture_sampler() -> SamplerDescriptor {
-    SamplerDescriptor {
-        mag_filter: FilterMode::Linear,
-        min_filter: FilterMode::Linear,
-        ..Default::default()
+// The import scale and axis conversion, baked into the vertex data so that anything built from
+// the meshes, like colliders, sees the converted model as well. Only the winding is left for
+// files whose conversion goes on the scene's root, see `root_transform`.
+struct ImportTransform {
+    up_axis: UpAxis,
+    scale: f32,
+    flip_winding: bool,
+}
+
+impl ImportTransform {
+    fn new(settings: &ObjLoaderSettings) -> Self {
+        if settings.transform_root && !settings.meshes_only {
+            return ImportTransform {
+                up_axis: UpAxis::Y,
+                scale: 1.0,
+                flip_winding: settings.flip_winding,
+            };
+        }
+        ImportTransform {
+            up_axis: settings.up_axis,
+            scale: settings.scale,
+            flip_winding: settings.flip_winding,
+        }
+    }
+
+    fn is_identity(&self) -> bool {
+        self.up_axis == UpAxis::Y && self.scale == 1.0 && !self.flip_winding
+    }
+
+    // Z-up is turned into Y-up by a rotation around X, written out as a swizzle so that no
+    // rounding error creeps into the positions.
+    fn rotate(&self, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        match self.up_axis {
+            UpAxis::Y => [x, y, z],
+            UpAxis::Z => [x, z, -y],
+        }
+    }
+
+    fn position(&self, position: [f32; 3]) -> [f32; 3] {
+        self.rotate(position).map(|c| c * self.scale)
+    }
+
+    fn apply(&self, mesh: &mut tobj::Mesh) {
+        for position in mesh.positions.chunks_exact_mut(3) {
+            let transformed = self.position([position[0], position[1], position[2]]);
+            position.copy_from_slice(&transformed);
+        }
+        // Normals only follow the rotation, and turn around along with the winding when a
+        // negative scale mirrors the model.
+        let sign = self.scale.signum();
+        for normal in mesh.normals.chunks_exact_mut(3) {
+            let transformed = self
+                .rotate([normal[0], normal[1], normal[2]])
+                .map(|c| c * sign);
+            normal.copy_from_slice(&transformed);
+        }
+        // Flipping a mirrored model's winding cancels out the reversal the mirroring needs.
+        if (self.scale < 0.0) != self.flip_winding {
+            rewind_faces(mesh);
+        }
     }
 }
 
-fn texture_label(texture: &String) -> String {
-    texture.clone()
+// The transform of the scene's root, which carries the import conversion when it isn't baked into
+// the vertex data. Turns Z-up into Y-up like `ImportTransform::rotate`.
+fn root_transform(settings: &ObjLoaderSettings) -> Transform {
+    if !settings.transform_root {
+        return Transform::identity();
+    }
+    let rotation = match settings.up_axis {
+        UpAxis::Y => Quat::IDENTITY,
+        UpAxis::Z => Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2),
+    };
+    Transform {
+        translation: Vec3::ZERO,
+        rotation,
+        scale: Vec3::splat(settings.scale),
+    }
 }
 
-fn material_label(material: &tobj::Material) -> String {
-    material.name.clone()
+fn rewind_faces(mesh: &mut tobj::Mesh) {
+    if mesh.num_face_indices.is_empty() {
+        for triangle in mesh.indices.chunks_exact_mut(3) {
+            triangle.reverse();
+        }
+        return;
+    }
+    let mut start = 0;
+    for &count in &mesh.num_face_indices {
+        let end = start + count as usize;
+        if let Some(face) = mesh.indices.get_mut(start..end) {
+            face.reverse();
+        }
+        start = end;
+    }
 }
 
-fn model_label(model: &tobj::Model) -> String {
-    model.name.clone()
+// Turns around the triangles whose winding disagrees with the normals given for their corners,
+// as left behind by mirrored parts baked into the vertex data. Returns how many were turned.
+// tobj counts the corners of every face, so untriangulated meshes are told apart by the counts.
+fn fix_winding(mesh: &mut tobj::Mesh) -> usize {
+    if mesh.normals.is_empty() || mesh.num_face_indices.iter().any(|&corners| corners != 3) {
+        return 0;
+    }
+    let vector = |data: &[f32], i: u32| {
+        let i = i as usize * 3;
+        Vec3::new(data[i], data[i + 1], data[i + 2])
+    };
+    let (positions, normals) = (&mesh.positions, &mesh.normals);
+    let mut fixed = 0;
+    for triangle in mesh.indices.chunks_exact_mut(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| vector(positions, i));
+        let face_normal = (b - a).cross(c - a);
+        let vertex_normal = triangle
+            .iter()
+            .map(|&i| vector(normals, i))
+            .fold(Vec3::ZERO, |sum, normal| sum + normal);
+        if face_normal.dot(vertex_normal) < 0.0 {
+            triangle.swap(1, 2);
+            fixed += 1;
+        }
+    }
+    fixed
 }
 
-fn get_material_lib_paths<B: BufRead>(reader: &mut B) -> Result<Vec<String>> {
-    let mut materials = Vec::new();
-    for line in reader.lines() {
-        let line = line?;
-        let mut parts = line.split_whitespace();
-        match parts.next() {
-            Some("mtllib") => {
-                let mtllib = parts.next().context("invalid mtllib definition")?;
-                materials.push(mtllib.to_string());
-            }
-            _ => {}
+fn extend_bounds(bounds: &mut Option<(Vec3, Vec3)>, positions: &[f32]) {
+    for position in positions.chunks_exact(3) {
+        let position = Vec3::new(position[0], position[1], position[2]);
+        *bounds = Some(match *bounds {
+            Some((min, max)) => (min.min(position), max.max(position)),
+            None => (position, position),
+        });
+    }
+}
+
+fn combine_models(
+    models: Vec<ColoredModel>,
+    merging: MeshMerging,
+) -> Result<Vec<ColoredModel>, ObjError> {
+    let mut groups: Vec<(Option<usize>, Vec<ColoredModel>)> = Vec::new();
+    for model in models {
+        let material_id = model.0.mesh.material_id;
+        let group = match merging {
+            MeshMerging::All => groups.first_mut(),
+            _ => groups.iter_mut().find(|(id, _)| *id == material_id),
+        };
+        match group {
+            Some((_, group)) => group.push(model),
+            None => groups.push((material_id, vec![model])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .enumerate()
+        .map(|(i, (material_id, group))| {
+            let (mut mesh, colors) = concatenate_meshes(group)?;
+            mesh.material_id = material_id;
+            Ok((tobj::Model::new(mesh, format!("Combined{}", i)), colors))
+        })
+        .collect()
+}
+
+fn concatenate_meshes(models: Vec<ColoredModel>) -> Result<(tobj::Mesh, VertexColors), ObjError> {
+    // Attributes only some of the models have are filled in for the others so the combined
+    // buffers stay the same length.
+    let has_normals = models
+        .iter()
+        .any(|(model, _)| !model.mesh.normals.is_empty());
+    let has_texcoords = models
+        .iter()
+        .any(|(model, _)| !model.mesh.texcoords.is_empty());
+    let has_colors = models.iter().any(|(_, colors)| !colors.is_empty());
+    // An empty `num_face_indices` means every face is a triangle, which has to be spelled out
+    // once the faces follow those of a model counting them.
+    let counts_faces = models
+        .iter()
+        .any(|(model, _)| !model.mesh.num_face_indices.is_empty());
+
+    let mut combined = tobj::Mesh::empty();
+    let mut combined_colors = Vec::new();
+    for (model, mut colors) in models {
+        let mut mesh = model.mesh;
+        let vertex_count = mesh.positions.len() / 3;
+        let offset = combined.positions.len() / 3;
+        if offset + vertex_count > u32::MAX as usize {
+            return Err(ObjError::TooManyVertices);
+        }
+        let offset = offset as u32;
+
+        if has_normals && mesh.normals.is_empty() {
+            let positions = chunk_by::<f32, 3>(&mesh.positions, &model.name)?;
+            mesh.normals = smooth_normals(&positions, &mesh.indices)
+                .iter()
+                .flatten()
+                .copied()
+                .collect();
+        }
+        if has_texcoords && mesh.texcoords.is_empty() {
+            mesh.texcoords = vec![0.0; vertex_count * 2];
+        }
+        if has_colors && colors.is_empty() {
+            colors = vec![[1.0; 4]; vertex_count];
+        }
+        if counts_faces && mesh.num_face_indices.is_empty() {
+            mesh.num_face_indices = vec![3; mesh.indices.len() / 3];
+        }
+
+        combined.positions.append(&mut mesh.positions);
+        combined.normals.append(&mut mesh.normals);
+        combined.texcoords.append(&mut mesh.texcoords);
+        combined
+            .indices
+            .extend(mesh.indices.iter().map(|index| index + offset));
+        combined.num_face_indices.append(&mut mesh.num_face_indices);
+        combined_colors.append(&mut colors);
+    }
+    Ok((combined, combined_colors))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Topology {
+    Triangles,
+    Lines,
+    Points,
+}
+
+impl Topology {
+    fn primitive_topology(self) -> PrimitiveTopology {
+        match self {
+            Topology::Triangles => PrimitiveTopology::TriangleList,
+            Topology::Lines => PrimitiveTopology::LineList,
+            Topology::Points => PrimitiveTopology::PointList,
+        }
+    }
+
+    fn vertices_per_primitive(self) -> usize {
+        match self {
+            Topology::Triangles => 3,
+            Topology::Lines => 2,
+            Topology::Points => 1,
         }
     }
-    Ok(materials)
+}
+
+// tobj keeps two-vertex `l` elements as faces next to the triangles, and points come through as
+// lines from a vertex to itself (see `rewrite_elements`), so models containing them are split
+// into one model per topology.
+fn split_by_topology(models: Vec<ColoredModel>) -> Vec<(tobj::Model, VertexColors, Topology)> {
+    let mut split = Vec::with_capacity(models.len());
+    // tobj always ends with the model being parsed, which is empty if the file ended right
+    // after an `o` statement.
+    for (model, colors) in models
+        .into_iter()
+        .filter(|(model, _)| !model.mesh.indices.is_empty())
+    {
+        if !model.mesh.num_face_indices.contains(&2) {
+            split.push((model, colors, Topology::Triangles));
+            continue;
+        }
+
+        let mut triangles = Vec::new();
+        let mut lines = Vec::new();
+        let mut points = Vec::new();
+        let mut start = 0;
+        for &count in &model.mesh.num_face_indices {
+            let end = start + count as usize;
+            match model.mesh.indices.get(start..end) {
+                Some([a, b]) if a == b => points.push(*a),
+                Some(face @ [_, _]) => lines.extend_from_slice(face),
+                Some(face) => triangles.extend_from_slice(face),
+                None => break,
+            }
+            start = end;
+        }
+
+        let groups = vec![
+            (triangles, Topology::Triangles),
+            (lines, Topology::Lines),
+            (points, Topology::Points),
+        ];
+        for (indices, topology) in groups {
+            if !indices.is_empty() {
+                let mut mesh = model.mesh.clone();
+                mesh.indices = indices;
+                mesh.num_face_indices = Vec::new();
+                let model = tobj::Model::new(mesh, model.name.clone());
+                split.push((model, colors.clone(), topology));
+            }
+        }
+    }
+    split
+}
+
+// Checks that the attributes of a model line up with its positions and that its indices form
+// whole primitives within bounds. Lenient validation repairs the mesh and logs what was wrong.
+fn validate_mesh(
+    mesh: &mut tobj::Mesh,
+    colors: &mut VertexColors,
+    topology: Topology,
+    model: &str,
+    validation: Validation,
+    path: &Path,
+) -> Result<(), ObjError> {
+    let report = |error: ObjError| match validation {
+        Validation::Strict => Err(error),
+        Validation::Lenient => {
+            warn!("{}: repairing {}", path.display(), error);
+            Ok(())
+        }
+    };
+    let malformed = |detail: String| ObjError::MalformedVertexData {
+        mesh: model.to_string(),
+        detail,
+    };
+    let invalid = |reason: String| ObjError::Validation {
+        model: model.to_string(),
+        reason,
+    };
+
+    if !mesh.positions.len().is_multiple_of(3) {
+        report(malformed(format!(
+            "{} position components don't form whole vertices",
+            mesh.positions.len()
+        )))?;
+        mesh.positions.truncate(mesh.positions.len() / 3 * 3);
+    }
+    let vertex_count = mesh.positions.len() / 3;
+    // Colors come from the positions, so they only fall out of step with the truncation above.
+    colors.truncate(vertex_count);
+    if !mesh.normals.is_empty() && mesh.normals.len() != vertex_count * 3 {
+        report(malformed(format!(
+            "{} normal components for {} vertices",
+            mesh.normals.len(),
+            vertex_count
+        )))?;
+        // Missing normals are generated from the faces, which beats guessing the absent ones.
+        mesh.normals.clear();
+    }
+    if !mesh.texcoords.is_empty() && mesh.texcoords.len() != vertex_count * 2 {
+        report(malformed(format!(
+            "{} texcoord components for {} vertices",
+            mesh.texcoords.len(),
+            vertex_count
+        )))?;
+        mesh.texcoords.resize(vertex_count * 2, 0.0);
+    }
+
+    let vertices_per_primitive = topology.vertices_per_primitive();
+    if !mesh.indices.len().is_multiple_of(vertices_per_primitive) {
+        report(invalid(format!(
+            "{} indices don't form whole primitives",
+            mesh.indices.len()
+        )))?;
+        let whole = mesh.indices.len() / vertices_per_primitive * vertices_per_primitive;
+        mesh.indices.truncate(whole);
+    }
+    let in_range = |&index: &u32| (index as usize) < vertex_count;
+    if let Some(index) = mesh.indices.iter().find(|index| !in_range(index)) {
+        report(invalid(format!(
+            "index {} is out of range for {} vertices",
+            index, vertex_count
+        )))?;
+        mesh.indices = mesh
+            .indices
+            .chunks(vertices_per_primitive)
+            .filter(|primitive| primitive.iter().all(in_range))
+            .flatten()
+            .copied()
+            .collect();
+    }
+    // Infinite or NaN positions poison the bounds and generated normals of the whole mesh, so the
+    // primitives using them are dropped.
+    let finite = |&index: &u32| {
+        let index = index as usize * 3;
+        mesh.positions[index..index + 3]
+            .iter()
+            .all(|c| c.is_finite())
+    };
+    if let Some(index) = mesh.indices.iter().find(|index| !finite(index)) {
+        report(invalid(format!(
+            "vertex {} has a position that isn't finite",
+            index
+        )))?;
+        mesh.indices = mesh
+            .indices
+            .chunks(vertices_per_primitive)
+            .filter(|primitive| primitive.iter().all(finite))
+            .flatten()
+            .copied()
+            .collect();
+    }
+    Ok(())
+}
+
+fn build_mesh(
+    tobj_mesh: &tobj::Mesh,
+    name: &str,
+    topology: Topology,
+    with_tangents: bool,
+    colors: &[[f32; 4]],
+    settings: &ObjLoaderSettings,
+) -> Result<Mesh, ObjError> {
+    let mut positions = chunk_by::<f32, 3>(&tobj_mesh.positions, name)?;
+    let mut texcoords = chunk_by::<f32, 2>(&tobj_mesh.texcoords, name)?;
+    let mut colors = colors.to_vec();
+    let mut indices = tobj_mesh.indices.clone();
+    let generate_normals = tobj_mesh.normals.is_empty() && topology == Topology::Triangles;
+    // Flat shaded triangles can't share corners, so every corner gets a vertex of its own.
+    if generate_normals && settings.generated_normals == GeneratedNormals::Flat {
+        positions = unshare_vertices(&positions, &indices);
+        if !texcoords.is_empty() {
+            texcoords = unshare_vertices(&texcoords, &indices);
+        }
+        if !colors.is_empty() {
+            colors = unshare_vertices(&colors, &indices);
+        }
+        indices = (0..positions.len() as u32).collect();
+    }
+    let vertex_count = positions.len();
+
+    // Files without `vn` or `vt` lines still need attributes matching the position count, so
+    // normals are generated from the faces and texcoords default to the origin. Lines and points
+    // are unlit, so any normal will do for them.
+    let mut normals = if !generate_normals {
+        if tobj_mesh.normals.is_empty() {
+            vec![[0.0, 1.0, 0.0]; vertex_count]
+        } else {
+            chunk_by::<f32, 3>(&tobj_mesh.normals, name)?
+        }
+    } else if settings.generated_normals == GeneratedNormals::Flat {
+        flat_normals(&positions)
+    } else {
+        smooth_normals(&positions, &indices)
+    };
+
+    if texcoords.is_empty() {
+        texcoords = vec![[0.0, 0.0]; vertex_count];
+    }
+    if settings.flip_uv_v {
+        flip_uv_v(&mut texcoords);
+    }
+
+    if settings.dedup_vertices {
+        dedup_vertices(
+            &mut positions,
+            &mut normals,
+            &mut texcoords,
+            &mut colors,
+            &mut indices,
+        );
+        if positions.len() < vertex_count {
+            debug!(
+                "{}: deduplicated {} vertices to {}",
+                name,
+                vertex_count,
+                positions.len()
+            );
+        }
+    }
+
+    let mut mesh = Mesh::new(topology.primitive_topology());
+    // The shader needs a tangent attribute whenever a normal map is bound, so meshes without
+    // texcoords still get tangents, all falling back to a vector orthogonal to the normal.
+    if with_tangents && topology == Topology::Triangles {
+        let tangents = tangents(&positions, &normals, &texcoords, &indices);
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_TANGENT,
+            VertexAttributeValues::Float4(tangents),
+        );
+    }
+    if !colors.is_empty() {
+        mesh.set_attribute(Mesh::ATTRIBUTE_COLOR, VertexAttributeValues::Float4(colors));
+    }
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        VertexAttributeValues::Float3(positions),
+    );
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        VertexAttributeValues::Float3(normals),
+    );
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        VertexAttributeValues::Float2(texcoords),
+    );
+    mesh.set_indices(Some(mesh_indices(&indices)));
+    Ok(mesh)
+}
+
+fn unshare_vertices<T: Copy>(values: &[T], indices: &[u32]) -> Vec<T> {
+    indices.iter().map(|&i| values[i as usize]).collect()
+}
+
+// Every triangle's corners are its own, see `unshare_vertices`.
+fn flat_normals(positions: &[[f32; 3]]) -> Vec<[f32; 3]> {
+    let mut normals = Vec::with_capacity(positions.len());
+    for triangle in positions.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(Vec3::from);
+        let normal = (b - a).cross(c - a).try_normalize().unwrap_or(Vec3::Y);
+        normals.extend_from_slice(&[normal.into(); 3]);
+    }
+    normals
+}
+
+// Collapses vertices whose attributes are identical bit for bit, so that vertices differing in
+// their normal or UV along hard edges and seams are kept apart. Vertex colors are looked up by
+// position and can't tell identical vertices apart either.
+fn dedup_vertices(
+    positions: &mut Vec<[f32; 3]>,
+    normals: &mut Vec<[f32; 3]>,
+    texcoords: &mut Vec<[f32; 2]>,
+    colors: &mut Vec<[f32; 4]>,
+    indices: &mut [u32],
+) {
+    let mut unique = HashMap::with_capacity(positions.len());
+    let mut remap = Vec::with_capacity(positions.len());
+    for vertex in 0..positions.len() {
+        let key = (
+            vertex_key(positions[vertex]),
+            vertex_key(normals[vertex]),
+            vertex_key(texcoords[vertex]),
+            colors.get(vertex).copied().map(vertex_key),
+        );
+        let kept = unique.len();
+        let index = *unique.entry(key).or_insert(kept);
+        if index == kept {
+            positions[kept] = positions[vertex];
+            normals[kept] = normals[vertex];
+            texcoords[kept] = texcoords[vertex];
+            if !colors.is_empty() {
+                colors[kept] = colors[vertex];
+            }
+        }
+        remap.push(index as u32);
+    }
+    positions.truncate(unique.len());
+    normals.truncate(unique.len());
+    texcoords.truncate(unique.len());
+    colors.truncate(unique.len());
+    for index in indices {
+        *index = remap[*index as usize];
+    }
+}
+
+// Compares attributes exactly, except for telling apart zeros of either sign, which flat normals
+// computed from different triangles of the same side come out with.
+fn vertex_key<const N: usize>(values: [f32; N]) -> [u32; N] {
+    values.map(|value| (value + 0.0).to_bits())
+}
+
+// Most meshes are small enough for 16 bit indices, which halve the size of the index buffer.
+fn mesh_indices(indices: &[u32]) -> Indices {
+    match indices
+        .iter()
+        .map(|&i| u16::try_from(i))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(indices) => Indices::U16(indices),
+        Err(_) => Indices::U32(indices.to_vec()),
+    }
+}
+
+// Per-vertex tangents using Lengyel's method, with the bitangent handedness stored in `w`.
+// Triangles that are degenerate in UV space are skipped, and vertices left without a usable
+// tangent get an arbitrary one orthogonal to their normal rather than NaNs.
+fn tangents(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    texcoords: &[[f32; 2]],
+    indices: &[u32],
+) -> Vec<[f32; 4]> {
+    let mut tangents = vec![Vec3::ZERO; positions.len()];
+    let mut bitangents = vec![Vec3::ZERO; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| i as usize);
+        if a >= positions.len() || b >= positions.len() || c >= positions.len() {
+            continue;
+        }
+        let edge1 = Vec3::from(positions[b]) - Vec3::from(positions[a]);
+        let edge2 = Vec3::from(positions[c]) - Vec3::from(positions[a]);
+        let (du1, dv1) = (
+            texcoords[b][0] - texcoords[a][0],
+            texcoords[b][1] - texcoords[a][1],
+        );
+        let (du2, dv2) = (
+            texcoords[c][0] - texcoords[a][0],
+            texcoords[c][1] - texcoords[a][1],
+        );
+        let determinant = du1 * dv2 - du2 * dv1;
+        if determinant.abs() <= f32::EPSILON {
+            continue;
+        }
+        let tangent = (edge1 * dv2 - edge2 * dv1) / determinant;
+        let bitangent = (edge2 * du1 - edge1 * du2) / determinant;
+        for &vertex in &[a, b, c] {
+            tangents[vertex] += tangent;
+            bitangents[vertex] += bitangent;
+        }
+    }
+
+    normals
+        .iter()
+        .zip(tangents)
+        .zip(bitangents)
+        .map(|((&normal, tangent), bitangent)| {
+            let normal = Vec3::from(normal);
+            // Gram-Schmidt orthogonalize against the normal.
+            let tangent = (tangent - normal * normal.dot(tangent))
+                .try_normalize()
+                .unwrap_or_else(|| orthogonal(normal));
+            let handedness = if normal.cross(tangent).dot(bitangent) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            [tangent.x, tangent.y, tangent.z, handedness]
+        })
+        .collect()
+}
+
+fn orthogonal(normal: Vec3) -> Vec3 {
+    let axis = if normal.x.abs() < 0.9 {
+        Vec3::X
+    } else {
+        Vec3::Y
+    };
+    normal.cross(axis).try_normalize().unwrap_or(Vec3::X)
+}
+
+fn smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| i as usize);
+        if a >= positions.len() || b >= positions.len() || c >= positions.len() {
+            continue;
+        }
+        let (pa, pb, pc) = (
+            Vec3::from(positions[a]),
+            Vec3::from(positions[b]),
+            Vec3::from(positions[c]),
+        );
+        // Left unnormalized so larger faces contribute more to the shared vertices.
+        let face_normal = (pb - pa).cross(pc - pa);
+        normals[a] += face_normal;
+        normals[b] += face_normal;
+        normals[c] += face_normal;
+    }
+    normals
+        .into_iter()
+        .map(|normal| normal.try_normalize().unwrap_or(Vec3::Y).into())
+        .collect()
+}
+
+// The length is checked up front, so the chunks copy straight into a buffer of the final size.
+fn chunk_by<T: Copy + Default, const N: usize>(
+    v: &[T],
+    mesh: &str,
+) -> Result<Vec<[T; N]>, ObjError> {
+    if !v.len().is_multiple_of(N) {
+        return Err(ObjError::MalformedVertexData {
+            mesh: mesh.to_string(),
+            detail: format!("{} values don't divide into groups of {}", v.len(), N),
+        });
+    }
+    let mut chunks = Vec::with_capacity(v.len() / N);
+    chunks.extend(v.chunks_exact(N).map(|chunk| {
+        let mut array = [T::default(); N];
+        array.copy_from_slice(chunk);
+        array
+    }));
+    Ok(chunks)
+}
+
+// The options act on the bottom-left origin texture coordinates of the file, so a flip of V is
+// undone around them.
+fn bake_texture_transform(mesh: &mut Mesh, transform: ObjTextureTransform, flipped: bool) {
+    let texcoords = match mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0) {
+        Some(VertexAttributeValues::Float2(texcoords)) => texcoords,
+        _ => return,
+    };
+    let flip = |v: f32| if flipped { 1.0 - v } else { v };
+    for uv in texcoords {
+        uv[0] = uv[0] * transform.scale.x + transform.offset.x;
+        uv[1] = flip(flip(uv[1]) * transform.scale.y + transform.offset.y);
+    }
+}
+
+fn flip_uv_v(texcoords: &mut [[f32; 2]]) {
+    for uv in texcoords {
+        uv[1] = 1.0 - uv[1];
+    }
+}
+
+// Lists the textures of a material with the color space they're sampled in.
+fn material_textures<'a>(
+    material: &'a tobj::Material,
+    settings: &ObjLoaderSettings,
+) -> Vec<(&'a str, TextureKind)> {
+    TextureSlot::ALL
+        .iter()
+        .filter_map(|&slot| Some((slot.texture(material)?, slot.kind(settings))))
+        .collect()
+}
+
+// Reading textures dominates load times of large models, so every texture is fetched at once and
+// decoded as soon as its bytes arrive, overlapping with the remaining reads. Registering the
+// textures needs the load context mutably, which happens in order once all of them are done.
+async fn load_textures<'a, 'b>(
+    materials: &[tobj::Material],
+    load_context: &'a mut LoadContext<'b>,
+    settings: &'a ObjLoaderSettings,
+) -> Result<LoadedTextures, ObjError> {
+    let parent = asset_dir(load_context.path());
+    let mut requests = Vec::new();
+    let mut labels = HashSet::new();
+    for material in materials {
+        for (texture, kind) in material_textures(material, settings) {
+            let texture = match TextureRef::new(parent, texture, kind) {
+                Ok(texture) => texture,
+                Err(e) if settings.strict_textures => return Err(e),
+                Err(e) => {
+                    warn!("{}: skipping texture, {}", load_context.path().display(), e);
+                    continue;
+                }
+            };
+            // Materials commonly share textures, which only need to be read and decoded once.
+            if labels.insert(texture.label.clone()) {
+                requests.push(texture);
+            }
+        }
+    }
+
+    // The maps packed into metallic roughness textures are read along with the rest, but only
+    // registered once packed.
+    let mut packed_refs = Vec::new();
+    let mut packed_labels = HashSet::new();
+    for material in materials {
+        let packed_ref = match MetallicRoughnessRef::new(parent, material) {
+            Ok(Some(packed_ref)) => packed_ref,
+            Ok(None) => continue,
+            Err(e) if settings.strict_textures => return Err(e),
+            Err(e) => {
+                warn!("{}: skipping texture, {}", load_context.path().display(), e);
+                continue;
+            }
+        };
+        if packed_labels.insert(packed_ref.label.clone()) {
+            packed_refs.push((&material.name, packed_ref));
+        }
+    }
+    let mut input_labels = HashSet::new();
+    let inputs = packed_refs
+        .iter()
+        .flat_map(|(_, packed_ref)| packed_ref.inputs())
+        .filter(|texture_ref| input_labels.insert(texture_ref.label.clone()))
+        .collect::<Vec<_>>();
+
+    let context: &LoadContext = load_context;
+    let loads = requests
+        .iter()
+        .chain(inputs.iter().copied())
+        .map(|texture_ref| {
+            Box::pin(async move {
+                let bytes = context.read_asset_bytes(&texture_ref.image_path).await;
+                decode_texture(texture_ref, bytes, settings)
+            })
+        })
+        .collect();
+    let mut textures = join_all(loads).await;
+    let input_textures = textures.split_off(requests.len());
+    let mut loaded = LoadedTextures::default();
+    for (texture_ref, texture) in requests.into_iter().zip(textures) {
+        let texture = match texture {
+            Ok(texture) => texture,
+            Err(e) if settings.strict_textures => return Err(e),
+            // A broken image shouldn't take the whole model down with it, so it is replaced by a
+            // checker that makes the problem visible.
+            Err(e @ ObjError::TextureDecode { .. }) => {
+                warn!("{}: {}", load_context.path().display(), e);
+                load_context
+                    .set_labeled_asset(&texture_ref.label, LoadedAsset::new(fallback_texture()));
+                continue;
+            }
+            // Textures that can't be read are left out, so their materials go without them.
+            Err(e) => {
+                warn!("{}: skipping texture, {}", load_context.path().display(), e);
+                continue;
+            }
+        };
+        let dependency = AssetPath::new(texture_ref.image_path, None);
+        loaded.count += 1;
+        loaded.memory += texture.data.len();
+        load_context.set_labeled_asset(
+            &texture_ref.label,
+            LoadedAsset::new(texture).with_dependency(dependency),
+        );
+    }
+
+    let mut decoded = HashMap::new();
+    for (texture_ref, texture) in inputs.into_iter().zip(input_textures) {
+        match texture {
+            Ok(texture) => {
+                decoded.insert(&texture_ref.label, texture);
+            }
+            Err(e) if settings.strict_textures => return Err(e),
+            Err(e) => warn!("{}: skipping texture, {}", load_context.path().display(), e),
+        }
+    }
+    for (material_name, packed_ref) in &packed_refs {
+        let texture = |texture_ref: &Option<TextureRef>| match texture_ref {
+            Some(texture_ref) => decoded.get(&texture_ref.label).map(Some),
+            None => Some(None),
+        };
+        // Packing only half of the maps would put full metallic or roughness in place of the
+        // other, so materials go without the packed texture instead.
+        let (roughness, metallic) = match (
+            texture(&packed_ref.roughness),
+            texture(&packed_ref.metallic),
+        ) {
+            (Some(roughness), Some(metallic)) => (roughness, metallic),
+            _ => continue,
+        };
+        let texture = match pack_metallic_roughness(roughness, metallic) {
+            Some(texture) => texture,
+            None => {
+                warn!(
+                    "{}: can't pack the metallic and roughness textures of material {}, which \
+                     need to be 8 bit images of the same size",
+                    load_context.path().display(),
+                    material_name
+                );
+                continue;
+            }
+        };
+        let dependencies = packed_ref
+            .inputs()
+            .map(|texture_ref| AssetPath::new(texture_ref.image_path.clone(), None))
+            .collect();
+        loaded.count += 1;
+        loaded.memory += texture.data.len();
+        load_context.set_labeled_asset(
+            &packed_ref.label,
+            LoadedAsset::new(texture).with_dependencies(dependencies),
+        );
+    }
+    Ok(loaded)
+}
+
+// The `map_Pr` roughness and `map_Pm` metallic textures of a material, which Bevy reads from the
+// green and blue channels of a single texture stored under `label`.
+pub(crate) struct MetallicRoughnessRef {
+    pub(crate) label: String,
+    roughness: Option<TextureRef>,
+    metallic: Option<TextureRef>,
+}
+
+impl MetallicRoughnessRef {
+    pub(crate) fn new(parent: &Path, material: &tobj::Material) -> Result<Option<Self>, ObjError> {
+        let texture = |key: &str| {
+            material
+                .unknown_param
+                .get(key)
+                .filter(|texture| !texture.is_empty())
+                .map(|texture| TextureRef::new(parent, texture, TextureKind::Linear))
+                .transpose()
+        };
+        let roughness = texture("map_Pr")?;
+        let metallic = texture("map_Pm")?;
+        if roughness.is_none() && metallic.is_none() {
+            return Ok(None);
+        }
+        let label = |texture_ref: &Option<TextureRef>| {
+            texture_ref
+                .as_ref()
+                .map_or("", |texture_ref| texture_ref.label.as_str())
+                .to_string()
+        };
+        Ok(Some(MetallicRoughnessRef {
+            label: format!(
+                "MetallicRoughness/{}+{}",
+                label(&roughness),
+                label(&metallic)
+            ),
+            roughness,
+            metallic,
+        }))
+    }
+
+    fn inputs(&self) -> impl Iterator<Item = &TextureRef> {
+        self.roughness.iter().chain(self.metallic.iter())
+    }
+}
+
+// Packs the first channel of each image into a texture, filling in full roughness or metallic
+// for a missing one, which the material's factors then scale. Only works for images of the same
+// size, decoded into four bytes per pixel as all 8 bit images are.
+fn pack_metallic_roughness(
+    roughness: Option<&Texture>,
+    metallic: Option<&Texture>,
+) -> Option<Texture> {
+    let size = roughness.or(metallic)?.size;
+    let pixel_count = (size.width * size.height) as usize;
+    for texture in roughness.iter().chain(metallic.iter()) {
+        if texture.size != size || texture.data.len() != pixel_count * 4 {
+            return None;
+        }
+    }
+    let channel =
+        |texture: Option<&Texture>, pixel: usize| texture.map_or(255, |t| t.data[pixel * 4]);
+    let mut data = Vec::with_capacity(pixel_count * 4);
+    for pixel in 0..pixel_count {
+        data.extend_from_slice(&[0, channel(roughness, pixel), channel(metallic, pixel), 255]);
+    }
+    let mut texture = Texture::new(size, TextureDimension::D2, data, TextureFormat::Rgba8Unorm);
+    texture.sampler = roughness.or(metallic)?.sampler;
+    Some(texture)
+}
+
+// The textures that decoded, and the bytes of texture data they take up.
+#[derive(Default)]
+struct LoadedTextures {
+    count: usize,
+    memory: usize,
+}
+
+// Polls all futures until every one of them has completed, returning their outputs in order.
+async fn join_all<F: Future + Unpin>(futures: Vec<F>) -> Vec<F::Output> {
+    let mut pending = futures.into_iter().map(|f| (f, None)).collect::<Vec<_>>();
+    future::poll_fn(|cx| {
+        let mut done = true;
+        for (future, output) in pending.iter_mut() {
+            if output.is_none() {
+                match Pin::new(future).poll(cx) {
+                    Poll::Ready(value) => *output = Some(value),
+                    Poll::Pending => done = false,
+                }
+            }
+        }
+        if done {
+            Poll::Ready(pending.drain(..).filter_map(|(_, output)| output).collect())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+type InstanceKey = (PrimitiveTopology, Vec<Option<Vec<u8>>>, Option<Vec<u8>>);
+
+// What makes two meshes the same. The vertex buffer interleaves the attributes in the order of a
+// hash map, which differs between meshes, so the attributes are compared one by one instead.
+fn instance_key(mesh: &Mesh) -> InstanceKey {
+    let attributes = [
+        Mesh::ATTRIBUTE_POSITION,
+        Mesh::ATTRIBUTE_NORMAL,
+        Mesh::ATTRIBUTE_UV_0,
+        Mesh::ATTRIBUTE_TANGENT,
+        Mesh::ATTRIBUTE_COLOR,
+    ]
+    .iter()
+    .map(|&name| {
+        mesh.attribute(name)
+            .map(|values| values.get_bytes().to_vec())
+    })
+    .collect();
+    (
+        mesh.primitive_topology(),
+        attributes,
+        mesh.get_index_buffer_bytes(),
+    )
+}
+
+// A magenta and black checker that stands out on any model.
+fn fallback_texture() -> Texture {
+    let magenta = [255, 0, 255, 255];
+    let black = [0, 0, 0, 255];
+    let data = [magenta, black, black, magenta].concat();
+    let mut texture = Texture::new(
+        Extent3d::new(2, 2, 1),
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    texture.sampler.mag_filter = FilterMode::Nearest;
+    texture.sampler.min_filter = FilterMode::Nearest;
+    texture.sampler.address_mode_u = AddressMode::Repeat;
+    texture.sampler.address_mode_v = AddressMode::Repeat;
+    texture
+}
+
+// A texture statement resolved against the OBJ's directory.
+pub(crate) struct TextureRef {
+    image_path: PathBuf,
+    pub(crate) label: String,
+    kind: TextureKind,
+    clamp: bool,
+    bump_multiplier: Option<f32>,
+}
+
+impl TextureRef {
+    pub(crate) fn new(parent: &Path, texture: &str, kind: TextureKind) -> Result<Self, ObjError> {
+        let options = parse_texture_options(texture);
+        let image_path = normalize_asset_path(parent, options.path)?;
+        let mut label = texture_label(&image_path, kind);
+        // The sampler is part of the texture, so clamped uses of an image need their own copy.
+        if options.clamp {
+            label.push_str("@clamp");
+        }
+        // Bevy has no normal map strength, so the multiplier is baked into a copy of the image.
+        let bump_multiplier = options
+            .bump_multiplier
+            .filter(|&multiplier| kind == TextureKind::Normal && multiplier != 1.0);
+        if let Some(multiplier) = bump_multiplier {
+            label.push_str(&format!("@bm{}", multiplier));
+        }
+        Ok(TextureRef {
+            image_path,
+            label,
+            kind,
+            clamp: options.clamp,
+            bump_multiplier,
+        })
+    }
+}
+
+fn decode_texture(
+    texture_ref: &TextureRef,
+    bytes: Result<Vec<u8>, AssetIoError>,
+    settings: &ObjLoaderSettings,
+) -> Result<Texture, ObjError> {
+    let image_path = &texture_ref.image_path;
+    let path = image_path.display().to_string();
+    let decode_error = |source: anyhow::Error| ObjError::TextureDecode {
+        path: path.clone(),
+        source,
+    };
+
+    let bytes = bytes.map_err(|e| match e {
+        AssetIoError::NotFound(_) => ObjError::TextureNotFound(path.clone()),
+        e => ObjError::TextureLoad {
+            path: path.clone(),
+            source: e.into(),
+        },
+    })?;
+
+    let extension = image_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .ok_or_else(|| decode_error(anyhow::Error::msg("missing file extension")))?;
+    let mut texture = Texture::from_buffer(&bytes, ImageType::Extension(extension))
+        .map_err(|e| decode_error(e.into()))?;
+    texture.sampler = settings.sampler;
+    if texture_ref.clamp {
+        texture.sampler.address_mode_u = AddressMode::ClampToEdge;
+        texture.sampler.address_mode_v = AddressMode::ClampToEdge;
+    }
+    texture.format = texture_ref.kind.format();
+    if texture_ref.kind == TextureKind::Normal {
+        let multiplier = texture_ref.bump_multiplier;
+        if is_height_map(&texture) {
+            height_to_normal_map(&mut texture, multiplier.unwrap_or(1.0));
+        } else if let Some(multiplier) = multiplier {
+            scale_normal_map(&mut texture, multiplier, image_path);
+        }
+    }
+    Ok(texture)
+}
+
+// How deep the full range of a bump map reaches, relative to the size of the texture, scaled by
+// its `-bm` multiplier.
+const BUMP_DEPTH: f32 = 1.0 / 64.0;
+
+// Bump statements hold either a normal map or, as the MTL spec has it, a height map. Normal maps
+// are never gray, as flat normals are blue, so an image whose texels all are is taken as heights.
+fn is_height_map(texture: &Texture) -> bool {
+    let pixels = texture.size.width as usize * texture.size.height as usize;
+    texture.data.len() == pixels * 4
+        && texture
+            .data
+            .chunks_exact(4)
+            .all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2])
+}
+
+// Replaces the heights of a bump map by the normals of the surface they describe, from the slope
+// across the neighbors of every texel. Textures repeat, so the neighbors wrap around the edges.
+fn height_to_normal_map(texture: &mut Texture, multiplier: f32) {
+    let width = texture.size.width as usize;
+    let height = texture.size.height as usize;
+    let heights: Vec<f32> = texture
+        .data
+        .chunks_exact(4)
+        .map(|pixel| pixel[0] as f32 / 255.0)
+        .collect();
+    let height_at = |x: usize, y: usize| heights[y * width + x];
+    let scale_x = multiplier * BUMP_DEPTH * width as f32;
+    let scale_y = multiplier * BUMP_DEPTH * height as f32;
+    for (i, pixel) in texture.data.chunks_exact_mut(4).enumerate() {
+        let (x, y) = (i % width, i / width);
+        let (left, right) = ((x + width - 1) % width, (x + 1) % width);
+        let (above, below) = ((y + height - 1) % height, (y + 1) % height);
+        // Normal maps point +Y toward the top of the image, against the order of the rows.
+        let slope_x = (height_at(right, y) - height_at(left, y)) / 2.0 * scale_x;
+        let slope_y = (height_at(x, above) - height_at(x, below)) / 2.0 * scale_y;
+        let normal = Vec3::new(-slope_x, -slope_y, 1.0).normalize();
+        pixel[0] = encode_normal(normal.x);
+        pixel[1] = encode_normal(normal.y);
+        pixel[2] = encode_normal(normal.z);
+        pixel[3] = 255;
+    }
+}
+
+fn encode_normal(component: f32) -> u8 {
+    ((component + 1.0) / 2.0 * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+// Scales the tangent plane tilt of every normal, making `-bm 2` bumps twice as steep and `-bm 0`
+// flat, the way renderers with a normal map strength apply it.
+fn scale_normal_map(texture: &mut Texture, multiplier: f32, image_path: &Path) {
+    let pixels = texture.size.width as usize * texture.size.height as usize;
+    if texture.data.len() != pixels * 4 {
+        warn!(
+            "{}: can't apply bump multiplier {} to a normal map that isn't an 8 bit image",
+            image_path.display(),
+            multiplier
+        );
+        return;
+    }
+    let decode = |channel: u8| channel as f32 / 255.0 * 2.0 - 1.0;
+    for pixel in texture.data.chunks_exact_mut(4) {
+        let normal = Vec3::new(
+            decode(pixel[0]) * multiplier,
+            decode(pixel[1]) * multiplier,
+            decode(pixel[2]),
+        );
+        let normal = if normal.length_squared() > 0.0 {
+            normal.normalize()
+        } else {
+            Vec3::Z
+        };
+        pixel[0] = encode_normal(normal.x);
+        pixel[1] = encode_normal(normal.y);
+        pixel[2] = encode_normal(normal.z);
+    }
+}
+
+// Options given before the file name of a texture statement, e.g. `map_Kd -clamp on wood.png`.
+// tobj keeps everything after the keyword as the texture name.
+struct TextureOptions<'a> {
+    path: &'a str,
+    clamp: bool,
+    offset: [f32; 3],
+    scale: [f32; 3],
+    bump_multiplier: Option<f32>,
+}
+
+fn parse_texture_options(texture: &str) -> TextureOptions<'_> {
+    let mut options = TextureOptions {
+        path: texture.trim(),
+        clamp: false,
+        offset: [0.0; 3],
+        scale: [1.0; 3],
+        bump_multiplier: None,
+    };
+    while let Some((option, rest)) = next_token(options.path) {
+        // The number of arguments of each option, as listed in the MTL spec. `-o`, `-s` and
+        // `-t` take up to three numbers.
+        let arguments = match option {
+            "-blendu" | "-blendv" | "-bm" | "-boost" | "-cc" | "-clamp" | "-imfchan"
+            | "-texres" | "-type" => 1,
+            "-mm" => 2,
+            "-o" | "-s" | "-t" => 3,
+            _ => break,
+        };
+        let mut rest = rest;
+        let mut values = Vec::with_capacity(arguments);
+        for i in 0..arguments {
+            match next_token(rest) {
+                Some((value, after)) if i == 0 || value.parse::<f32>().is_ok() => {
+                    values.push(value);
+                    rest = after;
+                }
+                _ => break,
+            }
+        }
+        match option {
+            "-clamp" => options.clamp = values.first() == Some(&"on"),
+            "-bm" => options.bump_multiplier = values.first().and_then(|v| v.parse().ok()),
+            "-o" => vector_option(&mut options.offset, &values),
+            "-s" => vector_option(&mut options.scale, &values),
+            _ => {}
+        }
+        options.path = rest.trim_start();
+    }
+    options
+}
+
+// Components left out of `-o` and `-s` keep their defaults.
+fn vector_option(vector: &mut [f32; 3], values: &[&str]) {
+    for (component, value) in vector.iter_mut().zip(values) {
+        if let Ok(value) = value.parse() {
+            *component = value;
+        }
+    }
+}
+
+// Bevy's materials have no texture transform, so the offset and scale of the diffuse texture are
+// passed on for custom shaders to apply.
+fn texture_transform(material: &tobj::Material) -> Option<ObjTextureTransform> {
+    let options = parse_texture_options(TextureSlot::Diffuse.texture(material)?);
+    if options.offset == [0.0; 3] && options.scale == [1.0; 3] {
+        return None;
+    }
+    Some(ObjTextureTransform {
+        offset: Vec3::from(options.offset),
+        scale: Vec3::from(options.scale),
+    })
+}
+
+fn next_token(text: &str) -> Option<(&str, &str)> {
+    let text = text.trim_start();
+    if text.is_empty() {
+        return None;
+    }
+    let end = text.find(char::is_whitespace).unwrap_or(text.len());
+    Some(text.split_at(end))
+}
+
+// The directory references are resolved against, which is the asset root itself for files
+// placed there.
+pub(crate) fn asset_dir(path: &Path) -> &Path {
+    path.parent().unwrap_or_else(|| Path::new(""))
+}
+
+// Material library and texture paths are relative to the OBJ and often come from Windows tools,
+// so backslashes are treated as separators and `.`/`..` are resolved up front, rejecting a `..`
+// that would leave the asset root. Asset paths can't leave the asset root, so absolute paths
+// like `C:\textures\wood.png` are reduced to their file name next to the OBJ.
+fn normalize_asset_path(parent: &Path, reference: &str) -> Result<PathBuf, ObjError> {
+    let mut path = parent.to_path_buf();
+    let mut components = reference.split(['/', '\\']);
+    if is_absolute_reference(reference) {
+        if let Some(file_name) = components.next_back() {
+            path.push(file_name);
+        }
+        return Ok(path);
+    }
+    for component in components {
+        match component {
+            "" | "." => {}
+            ".." => {
+                if !path.pop() {
+                    return Err(ObjError::InvalidPath(reference.to_string()));
+                }
+            }
+            component => path.push(component),
+        }
+    }
+    Ok(path)
+}
+
+fn is_absolute_reference(reference: &str) -> bool {
+    let bytes = reference.as_bytes();
+    reference.starts_with(['/', '\\'])
+        || (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':')
+}
+
+// Labels are built from the normalized path, so a texture referenced in two different ways is
+// still only loaded once.
+fn texture_label(image_path: &Path, kind: TextureKind) -> String {
+    let path = image_path
+        .iter()
+        .map(|component| component.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    format!("{}{}", path, kind.label_suffix())
+}
+
+// Material names are only unique within a library, so they are prefixed with the library
+// they come from.
+fn material_label(material_lib: &str, material: &tobj::Material) -> String {
+    format!("{}/{}", material_lib, material.name)
+}
+
+// tobj names objects without an `o`/`g` statement "unnamed_object", and splits them into one
+// model per material. They are named after that material, which keeps their labels pointing at
+// the same geometry when a reload reorders the file, and after a hash of their geometry when they
+// have no material either.
+fn model_label(model: &tobj::Model, materials: &[tobj::Material]) -> String {
+    if !model.name.is_empty() && model.name != "unnamed_object" {
+        return model.name.clone();
+    }
+    match model.mesh.material_id.and_then(|i| materials.get(i)) {
+        Some(material) => format!("Model_{}", material.name),
+        None => format!("Model_{:016x}", geometry_hash(&model.mesh)),
+    }
+}
+
+// FNV-1a over the positions and indices, which unlike the standard library's hasher is the same
+// in every build, so labels don't change with the compiler either.
+fn geometry_hash(mesh: &tobj::Mesh) -> u64 {
+    let words = mesh
+        .positions
+        .iter()
+        .map(|p| (p + 0.0).to_bits())
+        .chain(std::iter::once(u32::MAX))
+        .chain(mesh.indices.iter().copied());
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for word in words {
+        for byte in word.to_le_bytes().iter() {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+const RESERVED_LABELS: &[&str] = &[
+    "Obj",
+    "Scene",
+    "Stats",
+    "DefaultMaterial",
+    "VertexColorMaterial",
+    "UnlitMaterial",
+    "MissingMaterial",
+];
+
+// Files may repeat object names, and tobj also splits an object into one model per `usemtl`, all
+// sharing the object's name. Repeated names get Blender style `.001` suffixes to keep labels
+// unique.
+fn unique_label(name: &str, used_labels: &mut HashSet<String>) -> String {
+    let mut label = name.to_string();
+    let mut suffix = 0;
+    while !used_labels.insert(label.clone()) {
+        suffix += 1;
+        label = format!("{}.{:03}", name, suffix);
+    }
+    label
+}
+
+fn scene_name(path: &Path) -> String {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.split('.').next())
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[derive(Default)]
+struct ObjScan {
+    material_libs: Vec<String>,
+    // Faces with more than three vertices, which tobj's fan triangulation gets wrong if concave.
+    polygon_faces: usize,
+    // Whether any `v` line carries a color, see `index_vertices`.
+    has_vertex_colors: bool,
+    // Polylines and points, which tobj can't represent as is.
+    needs_element_rewrite: bool,
+    has_normals: bool,
+    has_texcoords: bool,
+    has_smoothing_groups: bool,
+    usemtl_before_mtllib: bool,
+    // Every name given to `usemtl`.
+    material_names: HashSet<String>,
+    vertex_count: usize,
+    texcoord_count: usize,
+    normal_count: usize,
+    // Faces with fewer than three corners, or referring to elements that may not exist, which
+    // `validate_faces` looks at before tobj gets to reject the whole file.
+    has_suspect_faces: bool,
+    has_elements: bool,
+}
+
+// The colors of a model's vertices, or empty for files without vertex colors.
+type VertexColors = Vec<[f32; 4]>;
+
+type ColoredModel = (tobj::Model, VertexColors);
+
+// tobj drops the non-standard `v x y z r g b` colors. It only merges the corners of faces that
+// share all of their indices though, so every vertex it returns is still a single `v` line.
+// Files with vertex colors have each `v` line rewritten into its own index ahead of parsing,
+// which `restore_vertices` reads back afterwards to put in the line's position and color. Colors
+// stay apart on seams this way, where vertices with different colors share a position.
+struct IndexedVertices {
+    positions: Vec<[f32; 3]>,
+    colors: Vec<[f32; 4]>,
+}
+
+// Rewrites every `v` line into its index, spread over two coordinates to stay exact in an `f32`.
+// Lines whose position doesn't parse are left for tobj to report.
+fn index_vertices(bytes: &[u8]) -> (Vec<u8>, IndexedVertices) {
+    let mut rewritten = Vec::with_capacity(bytes.len());
+    let mut vertices = IndexedVertices {
+        positions: Vec::new(),
+        colors: Vec::new(),
+    };
+    for line in bytes.split(|&b| b == b'\n') {
+        let text = std::str::from_utf8(line).unwrap_or("");
+        let mut parts = text.split_whitespace();
+        if parts.next() == Some("v") {
+            let components = parts.map(|c| c.parse::<f32>().ok()).collect::<Vec<_>>();
+            if let [Some(x), Some(y), Some(z), rest @ ..] = components.as_slice() {
+                let index = vertices.positions.len();
+                vertices.positions.push([*x, *y, *z]);
+                vertices.colors.push(match rest {
+                    [Some(r), Some(g), Some(b)] => [*r, *g, *b, 1.0],
+                    _ => [1.0; 4],
+                });
+                rewritten.extend_from_slice(
+                    format!("v {} {} 0\n", index & 0xfffff, index >> 20).as_bytes(),
+                );
+                continue;
+            }
+        }
+        rewritten.extend_from_slice(line);
+        rewritten.push(b'\n');
+    }
+    (rewritten, vertices)
+}
+
+// Puts the positions of the `v` lines back into a mesh parsed from `index_vertices`, and returns
+// the colors of its vertices.
+fn restore_vertices(mesh: &mut tobj::Mesh, vertices: &IndexedVertices) -> VertexColors {
+    let mut colors = Vec::with_capacity(mesh.positions.len() / 3);
+    for position in mesh.positions.chunks_exact_mut(3) {
+        let index = position[0] as usize | (position[1] as usize) << 20;
+        position.copy_from_slice(&vertices.positions[index]);
+        colors.push(vertices.colors[index]);
+    }
+    colors
+}
+
+// tobj fan triangulates `l` elements with more than two vertices and drops `p` elements, so
+// polylines are rewritten into their segments and points into lines from a vertex to itself,
+// which `split_by_topology` turns back into points.
+fn rewrite_elements(bytes: &[u8]) -> Vec<u8> {
+    let mut rewritten = Vec::with_capacity(bytes.len());
+    for line in bytes.split(|&b| b == b'\n') {
+        let text = std::str::from_utf8(line).unwrap_or("");
+        let mut parts = text.split_whitespace();
+        match parts.next() {
+            Some("l") => {
+                let vertices = parts.collect::<Vec<_>>();
+                for segment in vertices.windows(2) {
+                    rewritten
+                        .extend_from_slice(format!("l {} {}\n", segment[0], segment[1]).as_bytes());
+                }
+            }
+            Some("p") => {
+                for vertex in parts {
+                    rewritten.extend_from_slice(format!("l {} {}\n", vertex, vertex).as_bytes());
+                }
+            }
+            _ => {
+                rewritten.extend_from_slice(line);
+                rewritten.push(b'\n');
+            }
+        }
+    }
+    rewritten
+}
+
+// Whether every index of a `v/vt/vn` face corner refers to an element the file defines, given
+// the number of each defined so far for relative indices and in the whole file for absolute ones.
+// Returns `None` for corners that don't parse, which are left for tobj to report.
+fn corner_in_range(corner: &str, counts: [usize; 3]) -> Option<bool> {
+    let mut in_range = true;
+    for (index, count) in corner.split('/').zip(counts.iter()) {
+        if index.is_empty() {
+            continue;
+        }
+        let index = index.parse::<isize>().ok()?;
+        in_range &= index != 0 && index.unsigned_abs() <= *count;
+    }
+    Some(in_range)
+}
+
+// tobj fails the whole file on a face referring to an element that doesn't exist, and turns faces
+// with two corners into lines. Both are reported as invalid models, and dropped unless
+// validation is strict. Returns the file without them, or `None` when there were none after all,
+// as the scan can only check relative indices against the elements before them.
+fn validate_faces(
+    bytes: &[u8],
+    scan: &ObjScan,
+    validation: Validation,
+    path: &Path,
+) -> Result<Option<Vec<u8>>, ObjError> {
+    let totals = [scan.vertex_count, scan.texcoord_count, scan.normal_count];
+    let mut counts = [0; 3];
+    let mut model = "unnamed_object";
+    let mut valid = Vec::with_capacity(bytes.len());
+    let mut dropped = 0;
+    for (number, line) in bytes.split(|&b| b == b'\n').enumerate() {
+        let text = std::str::from_utf8(line).unwrap_or("");
+        let mut parts = text.split_whitespace();
+        let reason = match parts.next() {
+            Some("v") => {
+                counts[0] += 1;
+                None
+            }
+            Some("vt") => {
+                counts[1] += 1;
+                None
+            }
+            Some("vn") => {
+                counts[2] += 1;
+                None
+            }
+            Some("o") | Some("g") => {
+                model = text.trim()[1..].trim();
+                if model.is_empty() {
+                    model = "unnamed_object";
+                }
+                None
+            }
+            Some("f") => {
+                let corners = parts.collect::<Vec<_>>();
+                let out_of_range = corners.iter().find(|corner| {
+                    let relative = corner.contains('-');
+                    corner_in_range(corner, if relative { counts } else { totals }) == Some(false)
+                });
+                if corners.len() < 3 {
+                    Some(format!(
+                        "face on line {} has {} corners",
+                        number + 1,
+                        corners.len()
+                    ))
+                } else {
+                    out_of_range.map(|corner| {
+                        format!(
+                            "face on line {} refers to {}, which doesn't exist",
+                            number + 1,
+                            corner
+                        )
+                    })
+                }
+            }
+            _ => None,
+        };
+        match reason {
+            Some(reason) => {
+                let error = ObjError::Validation {
+                    model: model.to_string(),
+                    reason,
+                };
+                if validation == Validation::Strict {
+                    return Err(error);
+                }
+                warn!("{}: dropping face, {}", path.display(), error);
+                dropped += 1;
+            }
+            None => {
+                valid.extend_from_slice(line);
+                valid.push(b'\n');
+            }
+        }
+    }
+    Ok((dropped > 0).then_some(valid))
+}
+
+// tobj ignores `s` statements and shares a vertex between all faces using it, which rules out hard
+// edges in generated normals. Every smoothing group is given a placeholder normal, and every face
+// outside of one a normal of its own, so that tobj splits the vertices along the group boundaries.
+// Smoothing the normals over the split vertices then leaves hard edges between groups and flat
+// faces. Faces before the first `s` statement are smoothed together, as without any groups.
+fn split_smoothing_groups(bytes: &[u8]) -> Vec<u8> {
+    let mut faces = Vec::with_capacity(bytes.len());
+    let mut groups = HashMap::new();
+    let mut normal_count = 0;
+    let mut group = Some(String::new());
+    for line in bytes.split(|&b| b == b'\n') {
+        let text = std::str::from_utf8(line).unwrap_or("");
+        let mut parts = text.split_whitespace();
+        match parts.next() {
+            Some("s") => {
+                group = match parts.next() {
+                    Some("off") | Some("0") | None => None,
+                    Some(name) => Some(name.to_string()),
+                };
+            }
+            Some("f") => {
+                let normal = match &group {
+                    Some(name) => *groups.entry(name.clone()).or_insert_with(|| {
+                        normal_count += 1;
+                        normal_count
+                    }),
+                    None => {
+                        normal_count += 1;
+                        normal_count
+                    }
+                };
+                faces.push(b'f');
+                for vertex in parts {
+                    let mut indices = vertex.split('/');
+                    let position = indices.next().unwrap_or("");
+                    let texcoord = indices.next().unwrap_or("");
+                    faces.extend_from_slice(
+                        format!(" {}/{}/{}", position, texcoord, normal).as_bytes(),
+                    );
+                }
+                faces.push(b'\n');
+                continue;
+            }
+            _ => {}
+        }
+        faces.extend_from_slice(line);
+        faces.push(b'\n');
+    }
+
+    let mut rewritten = "vn 0 0 0\n".repeat(normal_count).into_bytes();
+    rewritten.append(&mut faces);
+    rewritten
+}
+
+// tobj fan triangulates polygons from their first vertex, which folds concave ones over
+// themselves. Concave faces are rewritten into the triangles of an ear clipping ahead of parsing,
+// while convex ones, for which the fan is right, are left to tobj. Returns the rewritten file and
+// how many faces were clipped.
+fn clip_concave_faces(bytes: &[u8]) -> (Vec<u8>, usize) {
+    let mut rewritten = Vec::with_capacity(bytes.len());
+    let mut positions = Vec::new();
+    let mut clipped = 0;
+    for line in bytes.split(|&b| b == b'\n') {
+        let text = std::str::from_utf8(line).unwrap_or("");
+        let mut parts = text.split_whitespace();
+        match parts.next() {
+            Some("v") => {
+                let mut components = parts.map(|c| c.parse::<f32>().unwrap_or(0.0));
+                let mut component = || components.next().unwrap_or(0.0);
+                positions.push(Vec3::new(component(), component(), component()));
+            }
+            Some("f") => {
+                let corners = parts.collect::<Vec<_>>();
+                if let Some(triangles) = ear_clip(&corners, &positions) {
+                    for [a, b, c] in triangles {
+                        rewritten.extend_from_slice(
+                            format!("f {} {} {}\n", corners[a], corners[b], corners[c]).as_bytes(),
+                        );
+                    }
+                    clipped += 1;
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        rewritten.extend_from_slice(line);
+        rewritten.push(b'\n');
+    }
+    (rewritten, clipped)
+}
+
+// Triangulates a concave polygon by ear clipping in the plane it mostly lies in. Returns `None`
+// for triangles, convex polygons and faces whose positions can't be resolved.
+fn ear_clip(corners: &[&str], positions: &[Vec3]) -> Option<Vec<[usize; 3]>> {
+    if corners.len() <= 3 {
+        return None;
+    }
+    let points = corners
+        .iter()
+        .map(|corner| {
+            let index = corner.split('/').next()?.parse::<i64>().ok()?;
+            // Negative indices count back from the last vertex defined so far.
+            let index = if index < 0 {
+                positions.len() as i64 + index
+            } else {
+                index - 1
+            };
+            positions.get(usize::try_from(index).ok()?).copied()
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    // Newell's method gives the polygon's normal even when it isn't quite planar.
+    let mut normal = Vec3::ZERO;
+    for (i, &a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        normal += Vec3::new(
+            (a.y - b.y) * (a.z + b.z),
+            (a.z - b.z) * (a.x + b.x),
+            (a.x - b.x) * (a.y + b.y),
+        );
+    }
+    if normal == Vec3::ZERO {
+        return None;
+    }
+    // The polygon is projected along its dominant axis, swapping the remaining two when needed
+    // to keep it counterclockwise.
+    let abs = normal.abs();
+    let project = |p: Vec3| -> [f32; 2] {
+        if abs.x >= abs.y && abs.x >= abs.z {
+            if normal.x > 0.0 {
+                [p.y, p.z]
+            } else {
+                [p.z, p.y]
+            }
+        } else if abs.y >= abs.z {
+            if normal.y > 0.0 {
+                [p.z, p.x]
+            } else {
+                [p.x, p.z]
+            }
+        } else if normal.z > 0.0 {
+            [p.x, p.y]
+        } else {
+            [p.y, p.x]
+        }
+    };
+    let points = points.into_iter().map(project).collect::<Vec<_>>();
+    let cross = |a: [f32; 2], b: [f32; 2], c: [f32; 2]| {
+        (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+    };
+    let count = points.len();
+    let convex = (0..count)
+        .all(|i| cross(points[i], points[(i + 1) % count], points[(i + 2) % count]) >= 0.0);
+    if convex {
+        return None;
+    }
+
+    let mut remaining = (0..count).collect::<Vec<_>>();
+    let mut triangles = Vec::with_capacity(count - 2);
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        let ear = (0..len).find(|&i| {
+            let [a, b, c] = [
+                remaining[(i + len - 1) % len],
+                remaining[i],
+                remaining[(i + 1) % len],
+            ];
+            cross(points[a], points[b], points[c]) > 0.0
+                && remaining.iter().all(|&p| {
+                    p == a
+                        || p == b
+                        || p == c
+                        || cross(points[a], points[b], points[p]) < 0.0
+                        || cross(points[b], points[c], points[p]) < 0.0
+                        || cross(points[c], points[a], points[p]) < 0.0
+                })
+        });
+        // Self-intersecting or degenerate polygons may have no ear left, which leaves the rest
+        // to a fan.
+        let i = match ear {
+            Some(i) => i,
+            None => break,
+        };
+        triangles.push([
+            remaining[(i + len - 1) % len],
+            remaining[i],
+            remaining[(i + 1) % len],
+        ]);
+        remaining.remove(i);
+    }
+    for i in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+    Some(triangles)
+}
+
+fn scan_obj<B: BufRead>(reader: &mut B) -> Result<ObjScan> {
+    let mut scan = ObjScan::default();
+    for line in reader.lines() {
+        let line = line?;
+        let mut parts = line.split_whitespace();
+        let keyword = parts.next();
+        if let Some("f") | Some("l") | Some("p") = keyword {
+            scan.has_elements = true;
+        }
+        match keyword {
+            // The rest of the line is kept as is, since it may be one name containing spaces
+            // or several names; which one is only known once the files are looked up.
+            Some("mtllib") => {
+                let mtllib = line.trim()["mtllib".len()..].trim();
+                if mtllib.is_empty() {
+                    bail!("invalid mtllib definition");
+                }
+                scan.material_libs.push(mtllib.to_string());
+            }
+            Some("f") => {
+                let corners = parts.clone().count();
+                if corners > 3 {
+                    scan.polygon_faces += 1;
+                }
+                let counts = [scan.vertex_count, scan.texcoord_count, scan.normal_count];
+                scan.has_suspect_faces |= corners < 3
+                    || parts.any(|corner| corner_in_range(corner, counts) != Some(true));
+            }
+            Some("l") if parts.clone().count() > 2 => scan.needs_element_rewrite = true,
+            Some("p") => scan.needs_element_rewrite = true,
+            Some("vn") => {
+                scan.has_normals = true;
+                scan.normal_count += 1;
+            }
+            Some("vt") => {
+                scan.has_texcoords = true;
+                scan.texcoord_count += 1;
+            }
+            Some("s") => scan.has_smoothing_groups = true,
+            Some("usemtl") => {
+                scan.usemtl_before_mtllib |= scan.material_libs.is_empty();
+                // Named the way tobj reads it, as the rest of the line.
+                let name = line.trim()["usemtl".len()..].trim();
+                if !name.is_empty() {
+                    scan.material_names.insert(name.to_string());
+                }
+            }
+            Some("v") => {
+                scan.vertex_count += 1;
+                let components = parts
+                    .map(|c| c.parse::<f32>().ok())
+                    .collect::<Option<Vec<_>>>();
+                scan.has_vertex_colors |= components.is_some_and(|c| c.len() == 6);
+            }
+            _ => {}
+        }
+    }
+    Ok(scan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        MemoryAssetPlugin, MemoryAssets, Obj, ObjMesh, ObjPlugin, VERTEX_COLOR_PIPELINE_HANDLE,
+    };
+    use bevy::app::App;
+    use bevy::asset::{AddAsset, Asset, AssetPlugin, AssetServer, Assets, LoadState};
+    use bevy::prelude::{Children, MinimalPlugins, StandardMaterial};
+    use bevy::render::pipeline::{PipelineDescriptor, RenderPipelines};
+    use std::time::Duration;
+
+    // Parses `obj` as `model.obj` at the asset root, with `libraries` as the files around it.
+    fn parse_with(
+        obj: &str,
+        libraries: &[(&str, &str)],
+        settings: &ObjLoaderSettings,
+    ) -> Result<ParsedObj, ObjError> {
+        parse_obj(
+            obj.as_bytes(),
+            Path::new("model.obj"),
+            |path| {
+                libraries
+                    .iter()
+                    .find(|(name, _)| Path::new(name) == path)
+                    .map(|(_, contents)| contents.as_bytes().to_vec())
+            },
+            settings,
+        )
+    }
+
+    fn parse(obj: &str) -> ParsedObj {
+        parse_with(obj, &[], &ObjLoaderSettings::default()).unwrap()
+    }
+
+    // Loads `model.obj` out of `files` through an asset server, the way an app would, for what
+    // happens past parsing.
+    fn load(files: &[(&str, &[u8])], settings: ObjLoaderSettings) -> (App, Handle<Obj>) {
+        let assets = MemoryAssets::default();
+        for (path, bytes) in files {
+            assets.insert(*path, bytes.to_vec());
+        }
+        let mut builder = App::build();
+        builder
+            .add_plugins(MinimalPlugins)
+            .add_plugin(MemoryAssetPlugin { assets })
+            .add_plugin(AssetPlugin)
+            .add_asset::<Mesh>()
+            .add_asset::<Texture>()
+            .add_asset::<StandardMaterial>()
+            .add_asset::<Scene>()
+            .add_plugin(ObjPlugin {
+                settings,
+                ..Default::default()
+            });
+        let mut app = builder.app;
+        let handle: Handle<Obj> = app
+            .world
+            .get_resource::<AssetServer>()
+            .unwrap()
+            .load("model.obj#Obj");
+        for _ in 0..1000 {
+            app.update();
+            let server = app.world.get_resource::<AssetServer>().unwrap();
+            if server.get_load_state(&handle) == LoadState::Failed {
+                panic!("model.obj failed to load");
+            }
+            // Bevy can leave the load state at loading when it frees an unused labeled asset
+            // between the others arriving, so the `Obj` itself is waited for. Its siblings were
+            // all sent before it and arrive by the next update.
+            if app
+                .world
+                .get_resource::<Assets<Obj>>()
+                .unwrap()
+                .contains(&handle)
+            {
+                app.update();
+                return (app, handle);
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        panic!("model.obj didn't load in time");
+    }
+
+    fn asset<'a, T: Asset>(app: &'a App, handle: &Handle<T>) -> &'a T {
+        app.world
+            .get_resource::<Assets<T>>()
+            .unwrap()
+            .get(handle)
+            .expect("asset isn't loaded")
+    }
+
+    // Takes the scene out of the app, as its world can't be borrowed mutably through `Assets`.
+    fn take_scene(app: &mut App, handle: &Handle<Obj>) -> Scene {
+        let scene = asset(app, handle).scene.clone().unwrap();
+        let mut scenes = app.world.get_resource_mut::<Assets<Scene>>().unwrap();
+        scenes.remove(&scene).unwrap()
+    }
+
+    fn texcoords(mesh: &Mesh) -> &[[f32; 2]] {
+        match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+            Some(VertexAttributeValues::Float2(texcoords)) => texcoords,
+            _ => panic!("mesh has no texcoords"),
+        }
+    }
+
+    fn float3<'a>(mesh: &'a Mesh, name: &'static str) -> &'a [[f32; 3]] {
+        match mesh.attribute(name) {
+            Some(VertexAttributeValues::Float3(values)) => values,
+            _ => panic!("mesh has no {}", name),
+        }
+    }
+
+    fn indices(mesh: &Mesh) -> Vec<u32> {
+        match mesh.indices() {
+            Some(Indices::U16(indices)) => indices.iter().map(|&i| i as u32).collect(),
+            Some(Indices::U32(indices)) => indices.clone(),
+            None => panic!("mesh has no indices"),
+        }
+    }
+
+    // Encodes a `width` wide RGBA image as a PNG.
+    fn png(width: usize, rgba: &[u8]) -> Vec<u8> {
+        use flate2::write::ZlibEncoder;
+        use flate2::{Compression, Crc};
+        use std::io::Write;
+
+        let chunk = |png: &mut Vec<u8>, kind: &[u8], data: &[u8]| {
+            let mut crc = Crc::new();
+            crc.update(kind);
+            crc.update(data);
+            png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            png.extend_from_slice(kind);
+            png.extend_from_slice(data);
+            png.extend_from_slice(&crc.sum().to_be_bytes());
+        };
+        let height = rgba.len() / 4 / width;
+        let mut header = Vec::new();
+        header.extend_from_slice(&(width as u32).to_be_bytes());
+        header.extend_from_slice(&(height as u32).to_be_bytes());
+        // 8 bit RGBA, without interlacing.
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        for row in rgba.chunks(width * 4) {
+            encoder.write_all(&[0]).unwrap();
+            encoder.write_all(row).unwrap();
+        }
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        chunk(&mut png, b"IHDR", &header);
+        chunk(&mut png, b"IDAT", &encoder.finish().unwrap());
+        chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    // A unit cube made of quads, as Blender exports it.
+    const QUAD_CUBE: &str = "
+o Cube
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+v 1 0 1
+v 1 1 1
+v 0 1 1
+f 1 4 3 2
+f 5 6 7 8
+f 1 2 6 5
+f 2 3 7 6
+f 3 4 8 7
+f 4 1 5 8
+";
+
+    // The quad cube with a normal per face and, as some exporters write it, vertices of its own
+    // for every corner of every triangle.
+    fn unshared_cube() -> String {
+        let corners = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [1.0, 0.0, 1.0],
+            [1.0, 1.0, 1.0],
+            [0.0, 1.0, 1.0],
+        ];
+        let faces = [
+            ([0, 3, 2, 1], [0, 0, -1]),
+            ([4, 5, 6, 7], [0, 0, 1]),
+            ([0, 1, 5, 4], [0, -1, 0]),
+            ([1, 2, 6, 5], [1, 0, 0]),
+            ([2, 3, 7, 6], [0, 1, 0]),
+            ([3, 0, 4, 7], [-1, 0, 0]),
+        ];
+        let mut obj = String::new();
+        for (face, ([a, b, c, d], [x, y, z])) in faces.iter().enumerate() {
+            obj += &format!("vn {} {} {}\n", x, y, z);
+            for &corner in &[a, b, c, a, c, d] {
+                let [x, y, z] = corners[*corner];
+                obj += &format!("v {} {} {}\n", x, y, z);
+            }
+            let first = face * 6 + 1;
+            for triangle in 0..2 {
+                let vertex = first + triangle * 3;
+                obj += &format!(
+                    "f {}//{n} {}//{n} {}//{n}\n",
+                    vertex,
+                    vertex + 1,
+                    vertex + 2,
+                    n = face + 1
+                );
+            }
+        }
+        obj
+    }
+
+    // The corners of every triangle of a mesh, which stay the same however its vertices are
+    // indexed.
+    fn triangles(mesh: &Mesh) -> Vec<[[f32; 3]; 3]> {
+        let positions = float3(mesh, Mesh::ATTRIBUTE_POSITION);
+        indices(mesh)
+            .chunks(3)
+            .map(|t| [0, 1, 2].map(|i| positions[t[i] as usize]))
+            .collect()
+    }
+
+    const TEXTURED_TRIANGLE: &str = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0 0.25
+vt 1 0
+vt 0 1
+f 1/1 2/2 3/3
+";
+
+    #[test]
+    fn flips_v_by_default() {
+        let parsed = parse(TEXTURED_TRIANGLE);
+        assert_eq!(
+            texcoords(&parsed.meshes[0].mesh),
+            [[0.0, 0.75], [1.0, 1.0], [0.0, 0.0]]
+        );
+    }
+
+    const BROKEN_LIBRARY: &str = "
+mtllib broken.mtl
+usemtl Red
+f 1 2 3
+v 0 0 0
+v 1 0 0
+v 0 1 0
+";
+
+    #[test]
+    fn skips_material_libraries_that_fail_to_parse() {
+        let libraries = [("broken.mtl", "newmtl Red\nKd red\n")];
+        let parsed = parse_with(BROKEN_LIBRARY, &libraries, &ObjLoaderSettings::default()).unwrap();
+        assert!(parsed.materials.is_empty());
+        assert_eq!(parsed.meshes.len(), 1);
+
+        let strict = ObjLoaderSettings {
+            strict_materials: true,
+            ..Default::default()
+        };
+        let error = parse_with(BROKEN_LIBRARY, &libraries, &strict).err();
+        assert!(matches!(error, Some(ObjError::MtlParse { .. })));
+    }
+
+    #[test]
+    fn loads_dissolve_and_emissive_colors() {
+        let obj = "mtllib materials.mtl\nusemtl Lamp\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        let mtl = "newmtl Lamp\nKd 1 1 1\nd 0.25\nKe 1 0.5 0\n";
+        let (mut app, handle) = load(
+            &[
+                ("model.obj", obj.as_bytes()),
+                ("materials.mtl", mtl.as_bytes()),
+            ],
+            ObjLoaderSettings::default(),
+        );
+        let material = asset(&app, &asset(&app, &handle).materials[0]);
+        assert_eq!(material.base_color.a(), 0.25);
+        assert_eq!(material.emissive, Color::rgb(1.0, 0.5, 0.0));
+
+        // Blending only happens in the transparent pass.
+        let mut scene = take_scene(&mut app, &handle);
+        let world = &mut scene.world;
+        let visible = world.query::<&Visible>().iter(world).next().unwrap();
+        assert!(visible.is_transparent);
+    }
+
+    #[test]
+    fn triangulates_quads_unless_disabled() {
+        let parsed = parse(QUAD_CUBE);
+        assert_eq!(indices(&parsed.meshes[0].mesh).len(), 36);
+
+        let settings = ObjLoaderSettings {
+            triangulate: false,
+            ..Default::default()
+        };
+        let parsed = parse_with(QUAD_CUBE, &[], &settings).unwrap();
+        assert_eq!(indices(&parsed.meshes[0].mesh).len(), 24);
+    }
+
+    #[test]
+    fn fills_in_attributes_of_files_with_only_positions() {
+        let parsed = parse(QUAD_CUBE);
+        assert!(!parsed.has_normals && !parsed.has_texcoords);
+        let mesh = &parsed.meshes[0].mesh;
+        let vertex_count = mesh.count_vertices();
+        assert_eq!(float3(mesh, Mesh::ATTRIBUTE_NORMAL).len(), vertex_count);
+        assert_eq!(texcoords(mesh), vec![[0.0, 1.0]; vertex_count].as_slice());
+        for normal in float3(mesh, Mesh::ATTRIBUTE_NORMAL) {
+            assert!((Vec3::from(*normal).length() - 1.0).abs() < 1e-5);
+        }
+        assert!(indices(mesh).iter().all(|&i| (i as usize) < vertex_count));
+    }
+
+    #[test]
+    fn merges_materials_of_every_library() {
+        let obj = "
+mtllib walls.mtl
+mtllib floors.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+o Wall
+usemtl Brick
+f 1 2 3
+o Floor
+usemtl Tile
+f 1 3 2
+";
+        let libraries = [
+            ("walls.mtl", "newmtl Brick\nKd 1 0 0\n"),
+            ("floors.mtl", "newmtl Tile\nKd 0 0 1\n"),
+        ];
+        let parsed = parse_with(obj, &libraries, &ObjLoaderSettings::default()).unwrap();
+        let names: Vec<_> = parsed.materials.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["Brick", "Tile"]);
+        assert_eq!(parsed.material_libs, ["walls.mtl", "floors.mtl"]);
+        assert_eq!(parsed.meshes.len(), 2);
+        for mesh in &parsed.meshes {
+            let material = &parsed.materials[mesh.material.unwrap()];
+            let expected = if mesh.name == "Wall" { "Brick" } else { "Tile" };
+            assert_eq!(material.name, expected);
+        }
+    }
+
+    #[test]
+    fn triangulates_concave_polygons_within_their_outline() {
+        // An L made of one six sided face, which a fan from its first corner would spill out of.
+        let obj = "
+v 0 0 0
+v 2 0 0
+v 2 1 0
+v 1 1 0
+v 1 2 0
+v 0 2 0
+f 3 4 5 6 1 2
+";
+        let mesh = &parse(obj).meshes[0].mesh;
+        let indices = indices(mesh);
+        assert_eq!(indices.len(), 12);
+        let positions = float3(mesh, Mesh::ATTRIBUTE_POSITION);
+        let areas: Vec<f32> = indices
+            .chunks(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(positions[triangle[i] as usize]));
+                (b - a).cross(c - a).z / 2.0
+            })
+            .collect();
+        // Every triangle faces the same way and together they cover the L exactly.
+        assert!(areas.iter().all(|&area| area > 0.0));
+        assert!((areas.iter().sum::<f32>() - 3.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn splits_objects_by_material_under_their_own_name() {
+        let obj = "
+mtllib materials.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+o Chair
+usemtl Wood
+f 1 2 3
+usemtl Fabric
+f 1 3 2
+";
+        let libraries = [("materials.mtl", "newmtl Wood\nnewmtl Fabric\n")];
+        let parsed = parse_with(obj, &libraries, &ObjLoaderSettings::default()).unwrap();
+        let meshes: Vec<_> = parsed
+            .meshes
+            .iter()
+            .map(|mesh| {
+                let material = &parsed.materials[mesh.material.unwrap()];
+                (mesh.name.as_str(), material.name.as_str())
+            })
+            .collect();
+        assert_eq!(meshes, [("Chair", "Wood"), ("Chair.001", "Fabric")]);
+    }
+
+    #[test]
+    fn blends_translucent_materials() {
+        let obj = "
+mtllib glass.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+usemtl Stained
+f 1 2 3
+usemtl Frosted
+f 1 2 3
+usemtl Cutout
+f 1 2 3
+usemtl Clear
+f 1 2 3
+";
+        let mtl = "
+newmtl Stained
+d 0.5
+newmtl Frosted
+Tr 0.25
+newmtl Cutout
+map_d mask.png
+newmtl Clear
+";
+        let (mut app, handle) = load(
+            &[("model.obj", obj.as_bytes()), ("glass.mtl", mtl.as_bytes())],
+            ObjLoaderSettings::default(),
+        );
+        let materials = asset(&app, &handle).materials.clone();
+        let alphas: Vec<_> = materials
+            .iter()
+            .map(|material| asset(&app, material).base_color.a())
+            .collect();
+        assert_eq!(alphas, [0.5, 0.75, 1.0, 1.0]);
+
+        let mut scene = take_scene(&mut app, &handle);
+        let world = &mut scene.world;
+        let mut transparent: Vec<_> = world
+            .query::<(&Handle<StandardMaterial>, &Visible)>()
+            .iter(world)
+            .map(|(material, visible)| {
+                let index = materials.iter().position(|m| m == material).unwrap();
+                (index, visible.is_transparent)
+            })
+            .collect();
+        transparent.sort_unstable();
+        assert_eq!(transparent, [(0, true), (1, true), (2, true), (3, false)]);
+    }
+
+    #[test]
+    fn keeps_vertex_colors_apart_on_seams() {
+        // Two triangles meeting along an edge with vertices of their own, colored differently.
+        let obj = "
+v 0 0 0 1 0 0
+v 1 0 0 1 0 0
+v 0 1 0 1 0 0
+v 0 0 0 0 0 1
+v 1 0 0 0 0 1
+v 0 -1 0 0 0 1
+f 1 2 3
+f 4 6 5
+";
+        let parsed = parse(obj);
+        assert!(parsed.has_vertex_colors);
+        let mesh = &parsed.meshes[0].mesh;
+        let colors = match mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+            Some(VertexAttributeValues::Float4(colors)) => colors,
+            _ => panic!("mesh has no colors"),
+        };
+        let triangles: Vec<_> = indices(mesh)
+            .chunks(3)
+            .map(|triangle| {
+                triangle
+                    .iter()
+                    .map(|&i| colors[i as usize])
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(triangles[0], [[1.0, 0.0, 0.0, 1.0]; 3]);
+        assert_eq!(triangles[1], [[0.0, 0.0, 1.0, 1.0]; 3]);
+        // The positions are the file's rather than what the colors were matched up with.
+        assert_eq!(parsed.aabb_min, Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(parsed.aabb_max, Vec3::new(1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn renders_vertex_colors_without_a_material() {
+        let obj = "v 0 0 0 1 0 0\nv 1 0 0 0 1 0\nv 0 1 0 0 0 1\nf 1 2 3\n";
+        let (mut app, handle) = load(
+            &[("model.obj", obj.as_bytes())],
+            ObjLoaderSettings::default(),
+        );
+        let obj = asset(&app, &handle);
+        let mesh = asset(&app, &asset(&app, &obj.meshes[0]).mesh);
+        assert!(mesh.attribute(Mesh::ATTRIBUTE_COLOR).is_some());
+
+        let mut scene = take_scene(&mut app, &handle);
+        let world = &mut scene.world;
+        let (material, pipelines) = world
+            .query::<(&Handle<StandardMaterial>, &RenderPipelines)>()
+            .iter(world)
+            .next()
+            .unwrap();
+        // Bevy's pipeline ignores the colors, which would leave the mesh in the default grey.
+        let pipeline: Handle<PipelineDescriptor> = VERTEX_COLOR_PIPELINE_HANDLE.typed();
+        assert_eq!(pipelines.pipelines[0].pipeline, pipeline);
+        assert_eq!(asset(&app, material).base_color, Color::WHITE);
+    }
+
+    #[test]
+    fn counts_faces_of_triangle_meshes_combined_with_lines() {
+        let mut line = tobj::Mesh::empty();
+        line.positions = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+        line.indices = vec![0, 1];
+        line.num_face_indices = vec![2];
+        let mut triangle = tobj::Mesh::empty();
+        triangle.positions = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        triangle.indices = vec![0, 1, 2];
+        let models = vec![
+            (tobj::Model::new(line, "Line".to_string()), Vec::new()),
+            (
+                tobj::Model::new(triangle, "Triangle".to_string()),
+                Vec::new(),
+            ),
+        ];
+
+        let (combined, _) = concatenate_meshes(models).unwrap();
+        assert_eq!(combined.num_face_indices, [2, 3]);
+        let model = tobj::Model::new(combined, "Combined".to_string());
+        let split = split_by_topology(vec![(model, Vec::new())]);
+        let split: Vec<_> = split
+            .iter()
+            .map(|(model, _, topology)| (model.mesh.indices.clone(), *topology))
+            .collect();
+        assert_eq!(
+            split,
+            [
+                (vec![2, 3, 4], Topology::Triangles),
+                (vec![0, 1], Topology::Lines)
+            ]
+        );
+    }
+
+    #[test]
+    fn converts_shininess_to_perceptual_roughness() {
+        assert_eq!(shininess_to_roughness(0.0), 1.0);
+        let glossiest = (2.0f32 / 1002.0).powf(0.25);
+        assert!((shininess_to_roughness(1000.0) - glossiest).abs() < 1e-6);
+        // Exponents outside of the spec's range are clamped to it.
+        assert_eq!(shininess_to_roughness(-10.0), 1.0);
+        assert_eq!(
+            shininess_to_roughness(5000.0),
+            shininess_to_roughness(1000.0)
+        );
+        // The typical `Ns 250` of exporters is glossy, though far from a mirror.
+        assert!((shininess_to_roughness(250.0) - 0.2985).abs() < 1e-4);
+    }
+
+    #[test]
+    fn samples_color_textures_as_srgb_and_the_rest_linearly() {
+        let obj = "mtllib tiles.mtl\nusemtl Tiles\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        // The same image as both a color and a normal map, which needs a texture for each.
+        let mtl = "newmtl Tiles\nmap_Kd tiles.png\nmap_Ke tiles.png\nmap_Bump tiles.png\n";
+        let image = png(1, &[128, 128, 255, 255]);
+        let (app, handle) = load(
+            &[
+                ("model.obj", obj.as_bytes()),
+                ("tiles.mtl", mtl.as_bytes()),
+                ("tiles.png", &image),
+            ],
+            ObjLoaderSettings::default(),
+        );
+        let material = asset(&app, &asset(&app, &handle).materials[0]);
+        let format =
+            |texture: &Option<Handle<Texture>>| asset(&app, texture.as_ref().unwrap()).format;
+        assert_eq!(
+            format(&material.base_color_texture),
+            TextureFormat::Rgba8UnormSrgb
+        );
+        assert_eq!(
+            format(&material.emissive_texture),
+            TextureFormat::Rgba8UnormSrgb
+        );
+        assert_eq!(format(&material.normal_map), TextureFormat::Rgba8Unorm);
+        assert_eq!(material.base_color_texture, material.emissive_texture);
+    }
+
+    #[test]
+    fn reads_material_libraries_named_with_spaces() {
+        let material_names = |obj: &str, libraries: &[(&str, &str)]| {
+            let parsed = parse_with(obj, libraries, &ObjLoaderSettings::default()).unwrap();
+            parsed
+                .materials
+                .iter()
+                .map(|material| material.name.clone())
+                .collect::<Vec<_>>()
+        };
+        let triangle = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+
+        let obj = format!("mtllib my materials.mtl\n{}", triangle);
+        let names = material_names(&obj, &[("my materials.mtl", "newmtl Red\n")]);
+        assert_eq!(names, ["Red"]);
+
+        let obj = format!("mtllib red.mtl blue.mtl\n{}", triangle);
+        let libraries = [("red.mtl", "newmtl Red\n"), ("blue.mtl", "newmtl Blue\n")];
+        assert_eq!(material_names(&obj, &libraries), ["Red", "Blue"]);
+
+        let obj = format!("mtllib red.mtl \t\r\n{}", triangle.replace('\n', "\r\n"));
+        assert_eq!(
+            material_names(&obj, &[("red.mtl", "newmtl Red\n")]),
+            ["Red"]
+        );
+    }
+
+    #[test]
+    fn resolves_windows_style_references() {
+        let obj = "mtllib ..\\mats\\scene.mtl\nusemtl Wood\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        let parsed = parse_obj(
+            obj.as_bytes(),
+            Path::new("models/model.obj"),
+            |path| (path == Path::new("mats/scene.mtl")).then(|| b"newmtl Wood\n".to_vec()),
+            &ObjLoaderSettings::default(),
+        )
+        .unwrap();
+        assert_eq!(parsed.material_lib_paths, [PathBuf::from("mats/scene.mtl")]);
+        assert_eq!(parsed.meshes[0].material, Some(0));
+
+        // Absolute paths can't be followed out of the asset folder, so the file is looked for
+        // next to the OBJ.
+        let texture = normalize_asset_path(Path::new("models"), "C:\\img\\a.png").unwrap();
+        assert_eq!(texture, Path::new("models/a.png"));
+    }
+
+    #[test]
+    fn gives_every_material_its_own_texture() {
+        let mut obj = String::from("mtllib colors.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\n");
+        let mut mtl = String::new();
+        let mut images = Vec::new();
+        let colors = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]];
+        for (i, color) in colors.iter().enumerate() {
+            obj += &format!("usemtl Color{}\nf 1 2 3\n", i);
+            mtl += &format!("newmtl Color{}\nmap_Kd color{}.png\n", i, i);
+            images.push((format!("color{}.png", i), png(1, color)));
+        }
+        let mut files: Vec<(&str, &[u8])> = vec![
+            ("model.obj", obj.as_bytes()),
+            ("colors.mtl", mtl.as_bytes()),
+        ];
+        files.extend(
+            images
+                .iter()
+                .map(|(name, image)| (name.as_str(), image.as_slice())),
+        );
+        let (app, handle) = load(&files, ObjLoaderSettings::default());
+
+        // Textures are read concurrently and finish in any order, but are registered in order.
+        let materials = &asset(&app, &handle).materials;
+        assert_eq!(materials.len(), colors.len());
+        for (material, color) in materials.iter().zip(&colors) {
+            let texture = asset(&app, material).base_color_texture.as_ref().unwrap();
+            assert_eq!(asset(&app, texture).data, color);
+        }
+    }
+
+    #[test]
+    fn keeps_materials_in_place_when_one_loses_its_texture() {
+        let obj = "
+mtllib paints.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+usemtl Red
+f 1 2 3
+usemtl Green
+f 1 2 3
+usemtl Blue
+f 1 2 3
+usemtl Purple
+f 1 2 3
+";
+        let mtl = "
+newmtl Red
+Kd 1 0 0
+newmtl Green
+Kd 0 1 0
+map_Kd missing.png
+newmtl Blue
+Kd 0 0 1
+";
+        let (app, handle) = load(
+            &[
+                ("model.obj", obj.as_bytes()),
+                ("paints.mtl", mtl.as_bytes()),
+            ],
+            ObjLoaderSettings::default(),
+        );
+        let obj = asset(&app, &handle);
+        let colors: Vec<_> = obj
+            .meshes
+            .iter()
+            .map(|mesh| {
+                let material = asset(&app, mesh).material.as_ref().unwrap();
+                asset(&app, material).base_color
+            })
+            .collect();
+        // Purple is defined by no library, which gets the shared magenta placeholder.
+        let expected = [
+            Color::rgb(1.0, 0.0, 0.0),
+            Color::rgb(0.0, 1.0, 0.0),
+            Color::rgb(0.0, 0.0, 1.0),
+            Color::rgb(1.0, 0.0, 1.0),
+        ];
+        assert_eq!(colors, expected);
+        let green = asset(&app, &obj.materials[1]);
+        assert!(green.base_color_texture.is_none());
+    }
+
+    #[test]
+    fn falls_back_to_the_default_material_without_its_library() {
+        let obj = "mtllib missing.mtl\nusemtl Red\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        let settings = ObjLoaderSettings {
+            default_base_color: Color::rgb(0.2, 0.4, 0.6),
+            ..Default::default()
+        };
+        let (mut app, handle) = load(&[("model.obj", obj.as_bytes())], settings);
+        let obj = asset(&app, &handle);
+        assert!(obj.materials.is_empty());
+        assert_eq!(obj.meshes.len(), 1);
+
+        let mut scene = take_scene(&mut app, &handle);
+        let world = &mut scene.world;
+        let material = world
+            .query::<&Handle<StandardMaterial>>()
+            .iter(world)
+            .next()
+            .unwrap();
+        assert_eq!(asset(&app, material).base_color, Color::rgb(0.2, 0.4, 0.6));
+    }
+
+    #[test]
+    fn computes_tangents_for_normal_mapped_meshes() {
+        let tangents = |texcoords: &str| {
+            let obj = format!(
+                "mtllib bumps.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n{}vn 0 0 1\n\
+                 usemtl Bumpy\nf 1/1/1 2/2/1 3/3/1 4/4/1\nusemtl Flat\nf 1/1/1 3/3/1 4/4/1\n",
+                texcoords
+            );
+            let libraries = [(
+                "bumps.mtl",
+                "newmtl Bumpy\nmap_Bump bumps.png\nnewmtl Flat\n",
+            )];
+            let parsed = parse_with(&obj, &libraries, &ObjLoaderSettings::default()).unwrap();
+            assert!(parsed.meshes[1]
+                .mesh
+                .attribute(Mesh::ATTRIBUTE_TANGENT)
+                .is_none());
+            match parsed.meshes[0].mesh.attribute(Mesh::ATTRIBUTE_TANGENT) {
+                Some(VertexAttributeValues::Float4(tangents)) => tangents.clone(),
+                _ => panic!("mesh has no tangents"),
+            }
+        };
+
+        // The tangent follows the direction U grows in across the quad.
+        for tangent in tangents("vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n") {
+            let [x, y, z, w] = tangent;
+            assert!((Vec3::new(x, y, z) - Vec3::X).length() < 1e-5);
+            assert_eq!(w.abs(), 1.0);
+        }
+        // Texcoords without any area still give unit tangents along the surface.
+        for tangent in tangents("vt 0.5 0.5\nvt 0.5 0.5\nvt 0.5 0.5\nvt 0.5 0.5\n") {
+            let [x, y, z, w] = tangent;
+            let tangent = Vec3::new(x, y, z);
+            assert!((tangent.length() - 1.0).abs() < 1e-5);
+            assert!(tangent.z.abs() < 1e-5);
+            assert_eq!(w.abs(), 1.0);
+        }
+    }
+
+    #[test]
+    fn keeps_v_as_is_unless_flipped() {
+        let settings = ObjLoaderSettings {
+            flip_uv_v: false,
+            ..Default::default()
+        };
+        let parsed = parse_with(TEXTURED_TRIANGLE, &[], &settings).unwrap();
+        assert_eq!(
+            texcoords(&parsed.meshes[0].mesh),
+            [[0.0, 0.25], [1.0, 0.0], [0.0, 1.0]]
+        );
+    }
+
+    #[test]
+    fn bounds_meshes_after_converting_them() {
+        let obj = format!(
+            "{}o Triangle\nv 2 0 0\nv 3 0 0\nv 2 0 4\nf 9 10 11\n",
+            QUAD_CUBE
+        );
+        let settings = ObjLoaderSettings {
+            scale: 2.0,
+            ..Default::default()
+        };
+        let parsed = parse_with(&obj, &[], &settings).unwrap();
+        let bounds: Vec<_> = parsed
+            .meshes
+            .iter()
+            .map(|mesh| (mesh.aabb_min, mesh.aabb_max))
+            .collect();
+        assert_eq!(
+            bounds,
+            [
+                (Vec3::ZERO, Vec3::splat(2.0)),
+                (Vec3::new(4.0, 0.0, 0.0), Vec3::new(6.0, 0.0, 8.0)),
+            ]
+        );
+        assert_eq!(parsed.aabb_min, Vec3::ZERO);
+        assert_eq!(parsed.aabb_max, Vec3::new(6.0, 2.0, 8.0));
+
+        // Files without vertices are collapsed to the origin rather than spanning infinity.
+        let parsed = parse("o Empty\n");
+        assert_eq!((parsed.aabb_min, parsed.aabb_max), (Vec3::ZERO, Vec3::ZERO));
+    }
+
+    #[test]
+    fn uses_16_bit_indices_when_they_fit() {
+        let parsed = parse(QUAD_CUBE);
+        assert!(matches!(
+            parsed.meshes[0].mesh.indices(),
+            Some(Indices::U16(_))
+        ));
+        assert!(matches!(mesh_indices(&[0, 65535]), Indices::U16(_)));
+        assert!(matches!(mesh_indices(&[0, 65536]), Indices::U32(_)));
+    }
+
+    #[test]
+    fn parses_texture_options_off_the_path() {
+        let obj = "mtllib bricks.mtl\nusemtl Bricks\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        let mtl =
+            "newmtl Bricks\nmap_Kd -s 2 2 1 -o 0.5 0 0 bricks.png\nmap_bump -bm 0.3 normal.png\n";
+        let parsed =
+            parse_with(obj, &[("bricks.mtl", mtl)], &ObjLoaderSettings::default()).unwrap();
+        let material = &parsed.materials[0];
+
+        let diffuse = parse_texture_options(&material.diffuse_texture);
+        assert_eq!(diffuse.path, "bricks.png");
+        assert_eq!(
+            parsed.meshes[0].texture_transform,
+            Some(ObjTextureTransform {
+                offset: Vec3::new(0.5, 0.0, 0.0),
+                scale: Vec3::new(2.0, 2.0, 1.0),
+            })
+        );
+        let normal = parse_texture_options(&material.normal_texture);
+        assert_eq!(normal.path, "normal.png");
+        assert_eq!(normal.bump_multiplier, Some(0.3));
+
+        // Vector options may leave out components, and paths may contain spaces.
+        let options = parse_texture_options("-clamp on -s 3 -blendu off old bricks.png");
+        assert_eq!(options.path, "old bricks.png");
+        assert!(options.clamp);
+        assert_eq!(options.scale, [3.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn shares_mesh_handles_with_the_scene() {
+        let obj = format!("{}o Triangle\nf 1 2 3\n", QUAD_CUBE);
+        let (mut app, handle) = load(
+            &[("model.obj", obj.as_bytes())],
+            ObjLoaderSettings::default(),
+        );
+        let mut meshes: Vec<_> = asset(&app, &handle)
+            .meshes
+            .iter()
+            .map(|mesh| asset(&app, mesh).mesh.id)
+            .collect();
+
+        // Every instance of the scene renders these meshes rather than copies of them.
+        let mut scene = take_scene(&mut app, &handle);
+        let world = &mut scene.world;
+        let mut scene_meshes: Vec<_> = world
+            .query::<&Handle<Mesh>>()
+            .iter(world)
+            .map(|mesh| mesh.id)
+            .collect();
+        meshes.sort_unstable();
+        scene_meshes.sort_unstable();
+        assert_eq!(meshes.len(), 2);
+        assert_eq!(scene_meshes, meshes);
+    }
+
+    #[test]
+    fn deduplicates_vertices_unless_disabled() {
+        let obj = unshared_cube();
+        let duplicated = ObjLoaderSettings {
+            dedup_vertices: false,
+            ..Default::default()
+        };
+        let duplicated = parse_with(&obj, &[], &duplicated).unwrap();
+        let duplicated = &duplicated.meshes[0].mesh;
+        assert_eq!(duplicated.count_vertices(), 36);
+
+        // Corners are only shared by the faces of the same side, whose normals match.
+        let parsed = parse(&obj);
+        let deduplicated = &parsed.meshes[0].mesh;
+        assert_eq!(deduplicated.count_vertices(), 24);
+        assert_eq!(triangles(deduplicated), triangles(duplicated));
+    }
+
+    #[test]
+    fn reads_libraries_next_to_files_at_the_asset_root() {
+        assert_eq!(asset_dir(Path::new("scene.obj")), Path::new(""));
+        let obj = "mtllib scene.mtl\nusemtl Red\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        let requested = std::cell::RefCell::new(Vec::new());
+        let parsed = parse_obj(
+            obj.as_bytes(),
+            Path::new("scene.obj"),
+            |path| {
+                requested.borrow_mut().push(path.to_path_buf());
+                Some(b"newmtl Red\n".to_vec())
+            },
+            &ObjLoaderSettings::default(),
+        )
+        .unwrap();
+        assert_eq!(requested.into_inner(), [PathBuf::from("scene.mtl")]);
+        assert_eq!(parsed.meshes[0].material, Some(0));
+    }
+
+    #[test]
+    fn parses_bytes_without_an_asset_server() {
+        let obj = "mtllib wood.mtl\nusemtl Wood\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        let settings = ObjLoaderSettings::default();
+
+        // Without a directory there are no libraries to read, which leaves just the meshes.
+        let parsed = load_obj_from_bytes_blocking(obj.as_bytes(), None, &settings).unwrap();
+        assert_eq!(parsed.meshes.len(), 1);
+        assert!(parsed.materials.is_empty());
+        assert_eq!(parsed.meshes[0].material, None);
+
+        let dir = std::env::temp_dir().join(format!("bevy_obj_loader_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("wood.mtl"), "newmtl Wood\nmap_Kd ../wood.png\n").unwrap();
+        let parsed = load_obj_from_bytes_blocking(obj.as_bytes(), Some(&dir), &settings);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let parsed = parsed.unwrap();
+        assert_eq!(parsed.meshes[0].material, Some(0));
+        // Texture paths are left for the caller to resolve.
+        assert_eq!(parsed.materials[0].diffuse_texture, "../wood.png");
+    }
+
+    #[test]
+    fn welds_flat_shaded_corners_back_together() {
+        // Flat normals give every triangle corners of its own, which the corners of the same
+        // side share again once welded.
+        let unwelded = ObjLoaderSettings {
+            generated_normals: GeneratedNormals::Flat,
+            dedup_vertices: false,
+            ..Default::default()
+        };
+        let unwelded = parse_with(QUAD_CUBE, &[], &unwelded).unwrap();
+        let unwelded = &unwelded.meshes[0].mesh;
+        assert_eq!(unwelded.count_vertices(), 36);
+
+        let welded = ObjLoaderSettings {
+            generated_normals: GeneratedNormals::Flat,
+            ..Default::default()
+        };
+        let welded = parse_with(QUAD_CUBE, &[], &welded).unwrap();
+        let welded = &welded.meshes[0].mesh;
+        assert_eq!(welded.count_vertices(), 24);
+        assert_eq!(triangles(welded), triangles(unwelded));
+        let normals = |mesh: &Mesh| {
+            let normals = float3(mesh, Mesh::ATTRIBUTE_NORMAL);
+            indices(mesh)
+                .iter()
+                .map(|&i| normals[i as usize])
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(normals(welded), normals(unwelded));
+    }
+
+    #[test]
+    fn flips_winding_and_normals_when_asked() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        let flip = ObjLoaderSettings {
+            flip_winding: true,
+            ..Default::default()
+        };
+        let original = parse(obj);
+        let flipped = parse_with(obj, &[], &flip).unwrap();
+        let [original, flipped] = [&original, &flipped].map(|parsed| &parsed.meshes[0].mesh);
+
+        let mut reversed = triangles(original);
+        for triangle in &mut reversed {
+            triangle.reverse();
+        }
+        assert_eq!(triangles(flipped), reversed);
+        // Generated normals follow the winding, while those of the file point the way they
+        // were exported.
+        assert_eq!(
+            float3(flipped, Mesh::ATTRIBUTE_NORMAL),
+            [[0.0, 0.0, -1.0]; 3]
+        );
+        let with_normals = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1\n";
+        let flipped = parse_with(with_normals, &[], &flip).unwrap();
+        assert_eq!(
+            float3(&flipped.meshes[0].mesh, Mesh::ATTRIBUTE_NORMAL),
+            [[0.0, 0.0, 1.0]; 3]
+        );
+    }
+
+    #[test]
+    fn loads_gzip_compressed_files_like_plain_ones() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let gzip = |bytes: &[u8]| {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(bytes).unwrap();
+            encoder.finish().unwrap()
+        };
+        let obj = format!("mtllib cube.mtl\nusemtl Red\n{}", QUAD_CUBE);
+        let mtl = gzip(b"newmtl Red\nKd 1 0 0\n");
+        // The library was compressed without updating the `mtllib` statement.
+        let libraries = |path: &Path| (path == Path::new("cube.mtl.gz")).then(|| mtl.clone());
+        let settings = ObjLoaderSettings::default();
+        let model = Path::new("model.obj");
+        let plain = parse_obj(obj.as_bytes(), model, libraries, &settings).unwrap();
+        let compressed = parse_obj(&gzip(obj.as_bytes()), model, libraries, &settings).unwrap();
+
+        for parsed in [&plain, &compressed] {
+            assert_eq!(parsed.materials[0].diffuse, [1.0, 0.0, 0.0]);
+            assert_eq!(parsed.meshes[0].material, Some(0));
+        }
+        assert_eq!(
+            triangles(&compressed.meshes[0].mesh),
+            triangles(&plain.meshes[0].mesh)
+        );
+    }
+
+    #[test]
+    fn resolves_relative_references_within_the_asset_root() {
+        let resolve = |reference| normalize_asset_path(Path::new("models/trees"), reference);
+        assert_eq!(
+            resolve("../shared/common.mtl").unwrap(),
+            Path::new("models/shared/common.mtl")
+        );
+        assert_eq!(
+            resolve("./bark.mtl").unwrap(),
+            Path::new("models/trees/bark.mtl")
+        );
+        assert_eq!(
+            resolve("./leaves/../../shared/./textures/../common.png").unwrap(),
+            Path::new("models/shared/common.png")
+        );
+        assert_eq!(
+            resolve("../../common.mtl").unwrap(),
+            Path::new("common.mtl")
+        );
+        assert!(matches!(
+            resolve("../../../common.mtl"),
+            Err(ObjError::InvalidPath(reference)) if reference == "../../../common.mtl"
+        ));
+
+        // Libraries out of reach are skipped rather than looked for somewhere else.
+        let obj = "mtllib ../common.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        let parsed = parse_with(
+            obj,
+            &[("common.mtl", "newmtl Red\n")],
+            &ObjLoaderSettings::default(),
+        );
+        assert!(parsed.unwrap().materials.is_empty());
+    }
+
+    #[test]
+    fn builds_attributes_of_large_files_at_their_final_size() {
+        let chunks = chunk_by::<f32, 3>(&[0.0; 3 * 1000], "mesh").unwrap();
+        assert_eq!((chunks.len(), chunks.capacity()), (1000, 1000));
+        assert!(chunk_by::<f32, 3>(&[0.0; 4], "mesh").is_err());
+
+        let size = 256;
+        let mut obj = String::new();
+        for y in 0..size {
+            for x in 0..size {
+                obj += &format!("v {} {} 0\nvt {} {}\nvn 0 0 1\n", x, y, x, y);
+            }
+        }
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let [a, b, c, d] = [0, 1, size, size + 1].map(|i| y * size + x + i + 1);
+                obj += &format!(
+                    "f {0}/{0}/{1} {2}/{2}/{3} {4}/{4}/{5} {6}/{6}/{7}\n",
+                    a, a, b, b, d, d, c, c
+                );
+            }
+        }
+        let parsed = parse(&obj);
+        let mesh = &parsed.meshes[0].mesh;
+        assert_eq!(mesh.count_vertices(), size * size);
+        assert_eq!(indices(mesh).len(), (size - 1) * (size - 1) * 6);
+        for name in [
+            Mesh::ATTRIBUTE_POSITION,
+            Mesh::ATTRIBUTE_NORMAL,
+            Mesh::ATTRIBUTE_UV_0,
+        ] {
+            let (len, capacity) = match mesh.attribute(name).unwrap() {
+                VertexAttributeValues::Float2(values) => (values.len(), values.capacity()),
+                VertexAttributeValues::Float3(values) => (values.len(), values.capacity()),
+                VertexAttributeValues::Float4(values) => (values.len(), values.capacity()),
+                _ => unreachable!(),
+            };
+            assert_eq!(len, capacity, "{} was grown past its size", name);
+        }
+    }
+
+    #[test]
+    fn keeps_handles_on_their_objects_when_a_reload_reorders_the_file() {
+        const MTL: &str = "newmtl Red\nKd 1 0 0\nnewmtl Blue\nKd 0 0 1\n";
+        let tree = "o Tree\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n";
+        let bush = |x: f32| {
+            format!(
+                "o Bush\nv {0} 0 0\nv {1} 0 0\nv {0} 1 0\nf -3 -2 -1\n",
+                x,
+                x + 1.0
+            )
+        };
+        // Unnamed objects without a material, ahead of any `usemtl` as tobj carries the material
+        // over into the next object.
+        let loose = |z: f32| format!("g\nv 0 0 {0}\nv 1 0 {0}\nv 0 1 {0}\nf -3 -2 -1\n", z);
+        let unnamed = "v 0 0 5\nv 1 0 5\nv 0 1 5\nv 0 0 7\nv 1 0 7\nv 0 1 7\n\
+            usemtl Red\nf -6 -5 -4\nusemtl Blue\nf -3 -2 -1\n";
+        // The unnamed faces go before the named objects, as tobj gives them the name of the
+        // object before them.
+        let original = format!(
+            "mtllib model.mtl\n{}{}{}{}{}",
+            loose(20.0),
+            loose(30.0),
+            unnamed,
+            tree,
+            bush(10.0)
+        );
+        // A reload writes the new file over the handles of the old one, so loading each version
+        // shows what every handle points at before and after it.
+        let positions = |obj: &str| {
+            let (app, handle) = load(
+                &[("model.obj", obj.as_bytes()), ("model.mtl", MTL.as_bytes())],
+                ObjLoaderSettings::default(),
+            );
+            let server = app.world.get_resource::<AssetServer>().unwrap();
+            asset(&app, &handle)
+                .meshes
+                .iter()
+                .map(|obj_mesh| {
+                    let label = asset(&app, obj_mesh).name.clone();
+                    let mesh = server.get_handle::<Mesh, _>(&*format!("model.obj#{}", label));
+                    let positions = float3(asset(&app, &mesh), Mesh::ATTRIBUTE_POSITION).to_vec();
+                    (label, positions)
+                })
+                .collect::<HashMap<_, _>>()
+        };
+        let before = positions(&original);
+        assert_eq!(before.len(), 6);
+        assert!(before.contains_key("Model_Red") && before.contains_key("Model_Blue"));
+
+        // Bush moves and the file is written out in a different order.
+        let unnamed = unnamed
+            .replace("usemtl Red\nf -6 -5 -4\n", "")
+            .replace("f -3 -2 -1\n", "f -3 -2 -1\nusemtl Red\nf -6 -5 -4\n");
+        let reordered = format!(
+            "mtllib model.mtl\n{}{}{}{}{}",
+            loose(30.0),
+            loose(20.0),
+            unnamed,
+            bush(20.0),
+            tree
+        );
+        let mut after = positions(&reordered);
+        assert_eq!(after.len(), before.len());
+        assert_eq!(after.remove("Bush").unwrap()[0], [20.0, 0.0, 0.0]);
+        for (label, positions) in after {
+            assert_eq!(
+                before[&label], positions,
+                "{} moved to other geometry",
+                label
+            );
+        }
+    }
+
+    #[test]
+    fn packs_metallic_and_roughness_maps_read_with_the_other_textures() {
+        let obj = "mtllib pbr.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\n\
+            usemtl Metal\nf 1 2 3\nusemtl Rough\nf 1 2 3\nusemtl Broken\nf 1 2 3\n";
+        let mtl = "newmtl Metal\nmap_Pr rough.png\nmap_Pm metal.png\n\
+            newmtl Rough\nmap_Pr rough.png\n\
+            newmtl Broken\nmap_Pr rough.png\nmap_Pm missing.png\n";
+        let rough = png(2, &[10, 0, 0, 255, 20, 0, 0, 255]);
+        let metal = png(2, &[200, 0, 0, 255, 100, 0, 0, 255]);
+        let (app, handle) = load(
+            &[
+                ("model.obj", obj.as_bytes()),
+                ("pbr.mtl", mtl.as_bytes()),
+                ("rough.png", &rough),
+                ("metal.png", &metal),
+            ],
+            ObjLoaderSettings::default(),
+        );
+
+        let materials = &asset(&app, &handle).materials;
+        let packed = materials
+            .iter()
+            .map(|material| {
+                let texture = asset(&app, material).metallic_roughness_texture.as_ref()?;
+                Some(asset(&app, texture).data.clone())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            packed[0].as_deref(),
+            Some(&[0, 10, 200, 255, 0, 20, 100, 255][..])
+        );
+        // A missing metallic map stands in as full metallic, but one that can't be read leaves
+        // the material without the packed texture.
+        assert_eq!(
+            packed[1].as_deref(),
+            Some(&[0, 10, 255, 255, 0, 20, 255, 255][..])
+        );
+        assert_eq!(packed[2], None);
+    }
+
+    #[test]
+    fn keeps_positional_labels_of_instanced_meshes() {
+        let triangle = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n";
+        let obj = format!("o First\n{0}o Second\n{0}", triangle);
+        let settings = ObjLoaderSettings {
+            instance_meshes: true,
+            ..Default::default()
+        };
+        let (mut app, handle) = load(&[("model.obj", obj.as_bytes())], settings);
+
+        let obj_meshes = &asset(&app, &handle).meshes;
+        let shared = obj_meshes
+            .iter()
+            .map(|mesh| asset(&app, mesh).mesh.id)
+            .collect::<HashSet<_>>();
+        assert_eq!((obj_meshes.len(), shared.len()), (2, 1));
+        // Meshes nobody holds a handle to are freed once loaded, so they are loaded the way an
+        // app would.
+        let server = app.world.get_resource::<AssetServer>().unwrap().clone();
+        let labels = ["Mesh0", "Mesh1", "First"];
+        let meshes = labels.map(|label| server.load::<Mesh, _>(&*format!("model.obj#{}", label)));
+        for _ in 0..1000 {
+            app.update();
+            let loaded = app.world.get_resource::<Assets<Mesh>>().unwrap();
+            if meshes.iter().all(|mesh| loaded.contains(mesh)) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        for (label, mesh) in labels.iter().zip(&meshes) {
+            assert_eq!(asset(&app, mesh).count_vertices(), 3, "{}", label);
+        }
+    }
+
+    #[test]
+    fn stores_meshes_once_without_index_labels() {
+        let obj = "o Triangle\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        let settings = ObjLoaderSettings {
+            mesh_index_labels: false,
+            ..Default::default()
+        };
+        let (app, handle) = load(&[("model.obj", obj.as_bytes())], settings);
+
+        let meshes = app.world.get_resource::<Assets<Mesh>>().unwrap();
+        let obj_mesh = asset(&app, &asset(&app, &handle).meshes[0]);
+        assert_eq!(meshes.len(), 1);
+        assert!(meshes.contains(&obj_mesh.mesh));
+    }
+
+    #[test]
+    fn follows_illumination_models_unless_disabled() {
+        let obj = "mtllib illum.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\n\
+            usemtl Sky\nf 1 2 3\nusemtl Plastic\nf 1 2 3\nusemtl Chrome\nf 1 2 3\n";
+        let mtl = "newmtl Sky\nillum 0\nKs 0.5 0.5 0.5\n\
+            newmtl Plastic\nillum 2\nKs 0.5 0.5 0.5\n\
+            newmtl Chrome\nillum 5\nKs 0.5 0.5 0.5\nPm 0.25\n";
+        let materials = |settings| {
+            let (app, handle) = load(
+                &[("model.obj", obj.as_bytes()), ("illum.mtl", mtl.as_bytes())],
+                settings,
+            );
+            asset(&app, &handle)
+                .materials
+                .iter()
+                .map(|material| {
+                    let material = asset(&app, material);
+                    (material.unlit, material.reflectance, material.metallic)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let followed = materials(ObjLoaderSettings::default());
+        assert_eq!(
+            followed,
+            [(true, 0.5, 0.0), (false, 0.5, 0.0), (false, 1.0, 0.25)]
+        );
+        let ignored = materials(ObjLoaderSettings {
+            illumination_models: false,
+            ..Default::default()
+        });
+        assert_eq!(
+            ignored,
+            [(false, 0.5, 0.0), (false, 0.5, 0.0), (false, 0.5, 0.25)]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_models_when_strict_and_repairs_them_otherwise() {
+        let parse_as = |obj: &str, validation| {
+            let settings = ObjLoaderSettings {
+                validation,
+                ..Default::default()
+            };
+            parse_with(obj, &[], &settings)
+        };
+        let quad = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\n";
+        let out_of_range = format!("{}f 1 2 3\nf 2 4 9\n", quad);
+        let not_finite = "v nan 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\nf 2 4 3\nf 1 2 3\n";
+        let too_few_corners = format!("{}f 1 2 3\nf 2 4\n", quad);
+        for obj in [&*out_of_range, not_finite, &*too_few_corners] {
+            let strict = parse_as(obj, Validation::Strict);
+            assert!(
+                matches!(strict, Err(ObjError::Validation { .. })),
+                "{}",
+                obj
+            );
+            // The broken face is dropped along with anything it would have become.
+            let lenient = parse_as(obj, Validation::Lenient).unwrap();
+            assert_eq!(lenient.meshes.len(), 1, "{}", obj);
+            let mesh = &lenient.meshes[0].mesh;
+            assert_eq!(indices(mesh).len(), 3, "{}", obj);
+            assert!(lenient.aabb_min.is_finite() && lenient.aabb_max.is_finite());
+        }
+
+        // tobj only fills in the normals of the corners that give one.
+        let mismatched = format!("{}vn 0 0 1\nf 1//1 2 3\n", quad);
+        let strict = parse_as(&mismatched, Validation::Strict);
+        assert!(matches!(strict, Err(ObjError::MalformedVertexData { .. })));
+        let lenient = parse_as(&mismatched, Validation::Lenient).unwrap();
+        let mesh = &lenient.meshes[0].mesh;
+        assert_eq!(float3(mesh, Mesh::ATTRIBUTE_NORMAL), [[0.0, 0.0, 1.0]; 3]);
+    }
+
+    #[test]
+    fn splits_vertices_between_smoothing_groups() {
+        let flat = parse(&format!("s off\n{}", QUAD_CUBE));
+        let mesh = &flat.meshes[0].mesh;
+        let normals = float3(mesh, Mesh::ATTRIBUTE_NORMAL);
+        let distinct = normals
+            .iter()
+            .map(|normal| normal.map(|c| (c + 0.0).to_bits()))
+            .collect::<HashSet<_>>();
+        assert_eq!((mesh.count_vertices(), distinct.len()), (24, 6));
+
+        let smooth = parse(&format!("s 1\n{}", QUAD_CUBE));
+        let mesh = &smooth.meshes[0].mesh;
+        assert_eq!(mesh.count_vertices(), 8);
+        let positions = float3(mesh, Mesh::ATTRIBUTE_POSITION);
+        for (position, normal) in positions.iter().zip(float3(mesh, Mesh::ATTRIBUTE_NORMAL)) {
+            // Every corner blends the normals of its three sides, leaning away from the center of
+            // the cube rather than along one side.
+            let outward = Vec3::from(*position) - Vec3::splat(0.5);
+            let normal = Vec3::from(*normal);
+            assert!((normal.length() - 1.0).abs() < 1e-5);
+            assert!(
+                (0..3).all(|axis| normal[axis] * outward[axis] > 0.0),
+                "{:?}",
+                normal
+            );
+        }
+    }
+
+    #[test]
+    fn turns_inside_out_triangles_of_mirrored_parts_around() {
+        // A copy of the cube mirrored along X, as baked in by exporters, with its normals mirrored
+        // but its winding left as is.
+        let cube = unshared_cube();
+        let mut mirrored = String::new();
+        for line in cube.lines() {
+            let mut parts = line.split_whitespace();
+            let keyword = parts.next().unwrap();
+            let values = parts.collect::<Vec<_>>();
+            mirrored += &match keyword {
+                "v" => format!(
+                    "v {} {} {}\n",
+                    -values[0].parse::<f32>().unwrap() - 2.0,
+                    values[1],
+                    values[2]
+                ),
+                "vn" => format!(
+                    "vn {} {} {}\n",
+                    -values[0].parse::<f32>().unwrap(),
+                    values[1],
+                    values[2]
+                ),
+                _ => {
+                    let corners = values.iter().map(|corner| {
+                        let (vertex, normal) = corner.split_once("//").unwrap();
+                        let vertex = vertex.parse::<usize>().unwrap() + 36;
+                        format!("{}//{}", vertex, normal.parse::<usize>().unwrap() + 6)
+                    });
+                    format!("f {}\n", corners.collect::<Vec<_>>().join(" "))
+                }
+            };
+        }
+        let obj = format!("o Cube\n{}o Mirrored\n{}", cube, mirrored);
+        let settings = ObjLoaderSettings {
+            fix_winding: true,
+            ..Default::default()
+        };
+        let parsed = parse_with(&obj, &[], &settings).unwrap();
+
+        assert_eq!(parsed.meshes.len(), 2);
+        for (parsed_mesh, center) in parsed.meshes.iter().zip([0.5, -2.5]) {
+            let center = Vec3::new(center, 0.5, 0.5);
+            let triangles = triangles(&parsed_mesh.mesh);
+            assert_eq!(triangles.len(), 12);
+            for [a, b, c] in triangles.iter().map(|t| t.map(Vec3::from)) {
+                let outward = (a + b + c) / 3.0 - center;
+                assert!(
+                    (b - a).cross(c - a).dot(outward) > 0.0,
+                    "{}",
+                    parsed_mesh.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn leaves_excluded_objects_out_of_the_scene() {
+        let triangle = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n";
+        let obj = ["body", "collision_box", "collision_hull"]
+            .iter()
+            .map(|name| format!("o {}\n{}", name, triangle))
+            .collect::<String>();
+        // The names of the objects in the scene and the excluded ones.
+        let load_with = |include: &[&str], exclude: &[&str]| {
+            let settings = ObjLoaderSettings {
+                include: include.iter().map(|pattern| pattern.to_string()).collect(),
+                exclude: exclude.iter().map(|pattern| pattern.to_string()).collect(),
+                ..Default::default()
+            };
+            let (mut app, handle) = load(&[("model.obj", obj.as_bytes())], settings);
+            let loaded = asset(&app, &handle);
+            assert_eq!(loaded.meshes.len(), 3);
+            let name = |mesh: &Handle<ObjMesh>| asset(&app, mesh).name.clone();
+            let names = loaded
+                .meshes
+                .iter()
+                .map(|mesh| (asset(&app, mesh).mesh.id, name(mesh)))
+                .collect::<HashMap<_, _>>();
+            let excluded = loaded.excluded_meshes.iter().map(name).collect::<Vec<_>>();
+            let mut scene = take_scene(&mut app, &handle);
+            let world = &mut scene.world;
+            let mut spawned = world
+                .query::<&Handle<Mesh>>()
+                .iter(world)
+                .map(|mesh| names[&mesh.id].clone())
+                .collect::<Vec<_>>();
+            spawned.sort();
+            (spawned, excluded)
+        };
+
+        let (spawned, excluded) = load_with(&[], &["collision_*"]);
+        assert_eq!(spawned, ["body"]);
+        assert_eq!(excluded, ["collision_box", "collision_hull"]);
+        // Exclusion takes precedence over inclusion.
+        let (spawned, excluded) = load_with(&["body", "collision_*"], &["*_hull"]);
+        assert_eq!(spawned, ["body", "collision_box"]);
+        assert_eq!(excluded, ["collision_hull"]);
+        let (spawned, excluded) = load_with(&["body"], &[]);
+        assert_eq!(spawned, ["body"]);
+        assert_eq!(excluded, ["collision_box", "collision_hull"]);
+    }
+
+    // The unshared cube stretched to span 0..100 on every axis, the way a Z-up model in
+    // centimeters comes in.
+    fn centimeter_cube() -> String {
+        unshared_cube()
+            .lines()
+            .map(|line| match line.strip_prefix("v ") {
+                Some(position) => {
+                    let position = position
+                        .split(' ')
+                        .map(|c| c.parse::<f32>().unwrap() * 100.0);
+                    let position = position.map(|c| c.to_string()).collect::<Vec<_>>();
+                    format!("v {}\n", position.join(" "))
+                }
+                None => format!("{}\n", line),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn puts_the_import_conversion_on_the_scene_root_when_asked() {
+        let obj = format!("o Cube\n{}", centimeter_cube());
+        let settings = ObjLoaderSettings {
+            scale: 0.01,
+            up_axis: UpAxis::Z,
+            transform_root: true,
+            ..Default::default()
+        };
+        let (mut app, handle) = load(&[("model.obj", obj.as_bytes())], settings);
+
+        // The meshes stay as the file has them.
+        let obj_mesh = asset(&app, &asset(&app, &handle).meshes[0]);
+        assert_eq!(obj_mesh.aabb_max, Vec3::splat(100.0));
+        let mut scene = take_scene(&mut app, &handle);
+        let world = &mut scene.world;
+        let mut roots = world.query::<(&Transform, &Children)>();
+        let root = *roots.iter(world).next().unwrap().0;
+        assert!(root
+            .mul_vec3(Vec3::new(0.0, 0.0, 100.0))
+            .abs_diff_eq(Vec3::Y, 1e-6));
+        assert!(root
+            .mul_vec3(Vec3::new(0.0, 100.0, 0.0))
+            .abs_diff_eq(-Vec3::Z, 1e-6));
+
+        // Baked into the vertices by default, leaving the root alone.
+        let settings = ObjLoaderSettings {
+            scale: 0.01,
+            up_axis: UpAxis::Z,
+            ..Default::default()
+        };
+        let (mut app, handle) = load(&[("model.obj", obj.as_bytes())], settings);
+        let obj_mesh = asset(&app, &asset(&app, &handle).meshes[0]);
+        assert_eq!(obj_mesh.aabb_max, Vec3::new(1.0, 1.0, 0.0));
+        let mut scene = take_scene(&mut app, &handle);
+        let world = &mut scene.world;
+        let mut roots = world.query::<(&Transform, &Children)>();
+        assert_eq!(*roots.iter(world).next().unwrap().0, Transform::identity());
+    }
+
+    #[test]
+    fn converts_z_up_centimeters_into_y_up_meters() {
+        let obj = format!("o Cube\n{}", centimeter_cube());
+        let settings = ObjLoaderSettings {
+            scale: 0.01,
+            up_axis: UpAxis::Z,
+            ..Default::default()
+        };
+        let parsed = parse_with(&obj, &[], &settings).unwrap();
+
+        // What was 0..100 on Z comes out as 0..1 on Y, with Y turned into -Z.
+        let mesh = &parsed.meshes[0].mesh;
+        let positions = float3(mesh, Mesh::ATTRIBUTE_POSITION);
+        let (min, max) = positions.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), &position| (min.min(position.into()), max.max(position.into())),
+        );
+        assert!(min.abs_diff_eq(Vec3::new(0.0, 0.0, -1.0), 1e-6));
+        assert!(max.abs_diff_eq(Vec3::new(1.0, 1.0, 0.0), 1e-6));
+        // A rotation doesn't mirror, so the triangles still face out with their normals.
+        let center = Vec3::new(0.5, 0.5, -0.5);
+        let triangles = triangles(mesh);
+        assert_eq!(triangles.len(), 12);
+        for [a, b, c] in triangles.iter().map(|t| t.map(Vec3::from)) {
+            let outward = (a + b + c) / 3.0 - center;
+            assert!((b - a).cross(c - a).dot(outward) > 0.0);
+        }
+        let normals = float3(mesh, Mesh::ATTRIBUTE_NORMAL);
+        for (&position, &normal) in positions.iter().zip(normals) {
+            let outward = Vec3::from(position) - center;
+            assert!(Vec3::from(normal).dot(outward) > 0.0);
+        }
+    }
+
+    #[test]
+    fn numbers_repeated_object_names() {
+        let obj = (0..3)
+            .map(|x| {
+                format!(
+                    "o Rock\nv {0} 0 0\nv {1} 0 0\nv {0} 1 0\nf -3 -2 -1\n",
+                    x,
+                    x + 1
+                )
+            })
+            .collect::<String>();
+        let (app, handle) = load(
+            &[("model.obj", obj.as_bytes())],
+            ObjLoaderSettings::default(),
+        );
+
+        let names = asset(&app, &handle)
+            .meshes
+            .iter()
+            .map(|mesh| asset(&app, mesh).name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Rock", "Rock.001", "Rock.002"]);
+        let server = app.world.get_resource::<AssetServer>().unwrap();
+        for (x, label) in names.iter().enumerate() {
+            let mesh = server.get_handle::<Mesh, _>(&*format!("model.obj#{}", label));
+            let positions = float3(asset(&app, &mesh), Mesh::ATTRIBUTE_POSITION);
+            assert_eq!(positions[0], [x as f32, 0.0, 0.0]);
+            let obj_mesh =
+                server.get_handle::<ObjMesh, _>(&*format!("model.obj#ObjMesh/{}", label));
+            assert_eq!(asset(&app, &obj_mesh).mesh, mesh);
+        }
+    }
+
+    #[test]
+    fn resolves_materials_used_before_their_library() {
+        let obj = "usemtl Red\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nmtllib colors.mtl\n";
+        let libraries = [(
+            "colors.mtl",
+            "newmtl Blue\nKd 0 0 1\nnewmtl Red\nKd 1 0 0\n",
+        )];
+        let parsed = parse_with(obj, &libraries, &ObjLoaderSettings::default()).unwrap();
+
+        let mesh = &parsed.meshes[0];
+        assert_eq!(mesh.missing_material, None);
+        let material = &parsed.materials[mesh.material.expect("mesh has no material")];
+        assert_eq!(
+            (material.name.as_str(), material.diffuse),
+            ("Red", [1.0, 0.0, 0.0])
+        );
+    }
 }
@@ -0,0 +1,502 @@
+use bevy::asset::{Asset, AssetPath, Assets, HandleUntyped, LoadContext};
+use bevy::ecs::system::ResMut;
+use bevy::log::warn;
+use bevy::pbr::render_graph::PBR_PIPELINE_HANDLE;
+use bevy::pbr::PbrBundle;
+use bevy::prelude::{Bundle, Color, Handle, Mesh, StandardMaterial, Texture, Visible};
+use bevy::reflect::TypeUuid;
+use bevy::render::pipeline::{CullMode, PipelineDescriptor, RenderPipelines};
+use bevy::render::shader::{Shader, ShaderSource, ShaderStages};
+
+use super::loader::{asset_dir, MetallicRoughnessRef, TextureKind, TextureRef};
+use super::{ColorSpace, ObjLoaderSettings};
+
+/// Converts MTL materials into the material asset the scene is rendered with.
+///
+/// `StandardMaterialConverter` is used unless another converter is registered with
+/// `ObjPlugin::with_material_converter`.
+pub trait MaterialConverter: Send + Sync + 'static {
+    type Material: Asset;
+    /// Components spawned for every mesh entity of the scene.
+    type Bundle: Bundle;
+
+    /// Converts `material`, or provides the fallback for meshes without a material when it is
+    /// `None`. Every material is converted once per label, so the result is shared by all meshes
+    /// using it. Meshes whose material converts to `None` are spawned without a material.
+    fn convert(
+        &self,
+        material: Option<&tobj::Material>,
+        context: &MaterialContext,
+    ) -> Option<Self::Material>;
+
+    /// Builds the components of a mesh entity from its converted material and the MTL material
+    /// it was converted from, if any.
+    fn bundle(
+        &self,
+        mesh: Handle<Mesh>,
+        material: Handle<Self::Material>,
+        source: Option<&tobj::Material>,
+        context: &MaterialContext,
+    ) -> Self::Bundle;
+}
+
+/// The PBR pipeline without back face culling, which `StandardMaterialConverter` renders double
+/// sided materials with. Added by `ObjPlugin` once `PbrPlugin` has built the pipeline it copies.
+pub const DOUBLE_SIDED_PIPELINE_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(PipelineDescriptor::TYPE_UUID, 6436295832183796973);
+
+/// The PBR pipeline multiplying the base color with the `Mesh::ATTRIBUTE_COLOR` of the mesh,
+/// which Bevy's own ignores. `StandardMaterialConverter` renders the meshes of files with vertex
+/// colors with it. Added along with `DOUBLE_SIDED_PIPELINE_HANDLE`.
+pub const VERTEX_COLOR_PIPELINE_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(PipelineDescriptor::TYPE_UUID, 11385701857181653951);
+
+/// `VERTEX_COLOR_PIPELINE_HANDLE` without back face culling.
+pub const DOUBLE_SIDED_VERTEX_COLOR_PIPELINE_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(PipelineDescriptor::TYPE_UUID, 2399495668629834940);
+
+pub(crate) fn add_pipelines(
+    pipelines: Option<ResMut<Assets<PipelineDescriptor>>>,
+    shaders: Option<ResMut<Assets<Shader>>>,
+) {
+    // Apps without rendering, like servers loading collision meshes, have no pipelines.
+    let (mut pipelines, mut shaders) = match (pipelines, shaders) {
+        (Some(pipelines), Some(shaders)) => (pipelines, shaders),
+        _ => return,
+    };
+    let pbr = match pipelines.get(PBR_PIPELINE_HANDLE) {
+        Some(pipeline) => pipeline.clone(),
+        None => return,
+    };
+    let vertex_color = vertex_color_pipeline(&pbr, &mut shaders).unwrap_or_else(|| {
+        warn!("vertex colors are not rendered, as the PBR shaders could not be extended");
+        pbr.clone()
+    });
+    let variants = [
+        (DOUBLE_SIDED_PIPELINE_HANDLE, &pbr, "obj_double_sided", true),
+        (
+            VERTEX_COLOR_PIPELINE_HANDLE,
+            &vertex_color,
+            "obj_vertex_color",
+            false,
+        ),
+        (
+            DOUBLE_SIDED_VERTEX_COLOR_PIPELINE_HANDLE,
+            &vertex_color,
+            "obj_double_sided_vertex_color",
+            true,
+        ),
+    ];
+    for (handle, pipeline, name, double_sided) in variants {
+        let mut pipeline = pipeline.clone();
+        pipeline.name = Some(name.to_string());
+        if double_sided {
+            pipeline.primitive.cull_mode = CullMode::None;
+        }
+        pipelines.set_untracked(handle, pipeline);
+    }
+}
+
+// Bevy's PBR shaders are patched rather than copied, so they stay in step with the Bevy version
+// in use. Returns `None` when they no longer look the way the patches expect.
+fn vertex_color_pipeline(
+    pbr: &PipelineDescriptor,
+    shaders: &mut Assets<Shader>,
+) -> Option<PipelineDescriptor> {
+    let vertex = patch_shader(
+        shaders.get(&pbr.shader_stages.vertex)?,
+        &[
+            (
+                "layout(location = 2) in vec2 Vertex_Uv;",
+                "layout(location = 2) in vec2 Vertex_Uv;
+layout(location = 4) in vec4 Vertex_Color;
+layout(location = 4) out vec4 v_Color;",
+            ),
+            (
+                "v_Uv = Vertex_Uv;",
+                "v_Uv = Vertex_Uv;
+    v_Color = Vertex_Color;",
+            ),
+        ],
+    )?;
+    let fragment = patch_shader(
+        shaders.get(pbr.shader_stages.fragment.as_ref()?)?,
+        &[
+            (
+                "layout(location = 2) in vec2 v_Uv;",
+                "layout(location = 2) in vec2 v_Uv;
+layout(location = 4) in vec4 v_Color;",
+            ),
+            (
+                "vec4 output_color = base_color;",
+                "vec4 output_color = base_color * v_Color;",
+            ),
+        ],
+    )?;
+    let mut pipeline = pbr.clone();
+    pipeline.shader_stages = ShaderStages {
+        vertex: shaders.add(vertex),
+        fragment: Some(shaders.add(fragment)),
+    };
+    Some(pipeline)
+}
+
+fn patch_shader(shader: &Shader, patches: &[(&str, &str)]) -> Option<Shader> {
+    let mut source = match &shader.source {
+        ShaderSource::Glsl(source) => source.clone(),
+        ShaderSource::Spirv(_) => return None,
+    };
+    for (from, to) in patches {
+        if !source.contains(from) {
+            return None;
+        }
+        source = source.replacen(from, to, 1);
+    }
+    Some(Shader::from_glsl(shader.stage, &source))
+}
+
+/// Information about the file a material is converted for.
+pub struct MaterialContext<'a, 'b> {
+    pub(crate) load_context: &'a LoadContext<'b>,
+    pub(crate) settings: &'a ObjLoaderSettings,
+    pub(crate) surface: bool,
+    pub(crate) has_vertex_colors: bool,
+}
+
+impl<'a, 'b> MaterialContext<'a, 'b> {
+    pub fn settings(&self) -> &ObjLoaderSettings {
+        self.settings
+    }
+
+    /// Whether the material is used by triangles, rather than by lines and points which have no
+    /// surface to shade and get a conversion of their own.
+    pub fn is_surface(&self) -> bool {
+        self.surface
+    }
+
+    /// Whether the file has vertex colors, which every one of its meshes then has, white where
+    /// the file gives none.
+    pub fn has_vertex_colors(&self) -> bool {
+        self.has_vertex_colors
+    }
+
+    /// Whether the faces using `material` should be rendered from both sides, because of
+    /// `ObjLoaderSettings::double_sided` or because it has a `map_d` cutout. Lines and points
+    /// have no sides.
+    pub fn is_double_sided(&self, material: Option<&tobj::Material>) -> bool {
+        self.surface
+            && (self.settings.double_sided
+                || material.is_some_and(|material| !material.dissolve_texture.is_empty()))
+    }
+
+    /// The handle of a texture of `material`, which has already been loaded in the color space
+    /// the slot is sampled in. Images that failed to decode are replaced by a magenta checker,
+    /// while those that couldn't be read give `None` unless `ObjLoaderSettings::strict_textures`
+    /// failed the load.
+    pub fn texture(&self, material: &tobj::Material, slot: TextureSlot) -> Option<Handle<Texture>> {
+        let texture = slot.texture(material)?;
+        let parent = asset_dir(self.load_context.path());
+        let label = TextureRef::new(parent, texture, slot.kind(self.settings))
+            .ok()?
+            .label;
+        self.loaded_texture(&label)
+    }
+
+    /// The texture Bevy reads metallic and roughness from, packed from the `map_Pm` and `map_Pr`
+    /// textures of `material`, with full metallic or roughness standing in for a missing one.
+    pub fn metallic_roughness_texture(&self, material: &tobj::Material) -> Option<Handle<Texture>> {
+        let parent = asset_dir(self.load_context.path());
+        let label = MetallicRoughnessRef::new(parent, material).ok()??.label;
+        self.loaded_texture(&label)
+    }
+
+    fn loaded_texture(&self, label: &str) -> Option<Handle<Texture>> {
+        if !self.load_context.has_labeled_asset(label) {
+            return None;
+        }
+        let path = AssetPath::new_ref(self.load_context.path(), Some(label));
+        Some(self.load_context.get_handle(path))
+    }
+}
+
+/// The texture statements of an MTL material that are loaded along with it, with the color space
+/// they are sampled in unless `ObjLoaderSettings::texture_color_spaces` says otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureSlot {
+    /// `map_Kd`, sampled as sRGB.
+    Diffuse,
+    /// `map_Bump` or `bump`, sampled linearly. Grayscale images are taken as height maps and
+    /// converted into normal maps.
+    Normal,
+    /// `map_Ks`, sampled linearly.
+    Specular,
+    /// `map_Ka`, sampled linearly.
+    Ambient,
+    /// `map_Ke`, sampled as sRGB.
+    Emissive,
+}
+
+impl TextureSlot {
+    pub(crate) const ALL: [TextureSlot; 5] = [
+        TextureSlot::Diffuse,
+        TextureSlot::Normal,
+        TextureSlot::Specular,
+        TextureSlot::Ambient,
+        TextureSlot::Emissive,
+    ];
+
+    /// The texture statement of `material` for this slot, including any texture options.
+    pub fn texture(self, material: &tobj::Material) -> Option<&str> {
+        let texture = match self {
+            TextureSlot::Diffuse => &material.diffuse_texture,
+            TextureSlot::Normal => &material.normal_texture,
+            TextureSlot::Specular => &material.specular_texture,
+            TextureSlot::Ambient => &material.ambient_texture,
+            // tobj has no dedicated field for `map_Ke`, so it ends up with the unknown parameters.
+            TextureSlot::Emissive => material.unknown_param.get("map_Ke")?,
+        };
+        Some(texture.as_str()).filter(|texture| !texture.is_empty())
+    }
+
+    pub(crate) fn kind(self, settings: &ObjLoaderSettings) -> TextureKind {
+        let color_space = settings
+            .texture_color_spaces
+            .iter()
+            .find(|(slot, _)| *slot == self)
+            .map(|&(_, color_space)| color_space);
+        match (self, color_space) {
+            (_, Some(ColorSpace::Srgb)) => TextureKind::Color,
+            (TextureSlot::Normal, _) => TextureKind::Normal,
+            (_, Some(ColorSpace::Linear)) => TextureKind::Linear,
+            (TextureSlot::Diffuse, None) | (TextureSlot::Emissive, None) => TextureKind::Color,
+            (TextureSlot::Specular, None) | (TextureSlot::Ambient, None) => TextureKind::Linear,
+        }
+    }
+}
+
+/// Converts MTL materials into `StandardMaterial`s rendered with a `PbrBundle`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StandardMaterialConverter;
+
+impl MaterialConverter for StandardMaterialConverter {
+    type Material = StandardMaterial;
+    type Bundle = PbrBundle;
+
+    fn convert(
+        &self,
+        material: Option<&tobj::Material>,
+        context: &MaterialContext,
+    ) -> Option<StandardMaterial> {
+        let settings = context.settings();
+        let mut converted = match material {
+            Some(material) if context.is_surface() => standard_material(material, context),
+            // Lines and points have no surface to shade, so they are drawn in the flat color of
+            // their material.
+            Some(material) => StandardMaterial {
+                base_color: material_color(material),
+                unlit: true,
+                ..Default::default()
+            },
+            None if !context.is_surface() => StandardMaterial {
+                base_color: settings.default_base_color,
+                unlit: true,
+                ..Default::default()
+            },
+            None if settings.default_material.is_some() => {
+                copy_material(settings.default_material.as_deref()?)
+            }
+            // The vertex color pipeline of `bundle` multiplies the colors with the base color, so
+            // colored meshes without a material get a plain white one to show them as they are.
+            None if context.has_vertex_colors() => StandardMaterial {
+                base_color: Color::WHITE,
+                ..Default::default()
+            },
+            None => StandardMaterial {
+                base_color: settings.default_base_color,
+                ..Default::default()
+            },
+        };
+        // Lights the back faces the double sided pipeline of `bundle` leaves unculled.
+        converted.double_sided |= context.is_double_sided(material);
+        Some(converted)
+    }
+
+    fn bundle(
+        &self,
+        mesh: Handle<Mesh>,
+        material: Handle<StandardMaterial>,
+        source: Option<&tobj::Material>,
+        context: &MaterialContext,
+    ) -> PbrBundle {
+        let mut bundle = PbrBundle {
+            mesh,
+            material,
+            visible: Visible {
+                is_visible: true,
+                is_transparent: source.is_some_and(is_transparent_material),
+            },
+            ..Default::default()
+        };
+        // `StandardMaterial::double_sided` only lights back faces from the right side, they are
+        // still culled by the pipeline.
+        let pipeline = match (context.is_double_sided(source), context.has_vertex_colors()) {
+            (false, false) => return bundle,
+            (true, false) => DOUBLE_SIDED_PIPELINE_HANDLE,
+            (false, true) => VERTEX_COLOR_PIPELINE_HANDLE,
+            (true, true) => DOUBLE_SIDED_VERTEX_COLOR_PIPELINE_HANDLE,
+        };
+        bundle.render_pipelines = RenderPipelines::from_handles(&[pipeline.typed()]);
+        bundle
+    }
+}
+
+// `StandardMaterial` doesn't implement `Clone`.
+fn copy_material(material: &StandardMaterial) -> StandardMaterial {
+    StandardMaterial {
+        base_color: material.base_color,
+        base_color_texture: material.base_color_texture.clone(),
+        roughness: material.roughness,
+        metallic: material.metallic,
+        metallic_roughness_texture: material.metallic_roughness_texture.clone(),
+        reflectance: material.reflectance,
+        normal_map: material.normal_map.clone(),
+        double_sided: material.double_sided,
+        occlusion_texture: material.occlusion_texture.clone(),
+        emissive: material.emissive,
+        emissive_texture: material.emissive_texture.clone(),
+        unlit: material.unlit,
+    }
+}
+
+fn standard_material(material: &tobj::Material, context: &MaterialContext) -> StandardMaterial {
+    let defaults = StandardMaterial::default();
+    let (mut metallic, mut roughness) = metallic_roughness(material, context.settings());
+    let metallic_roughness_texture = context.metallic_roughness_texture(material);
+    // Bevy scales the texture by the factors, so the maps are used as is unless the file gives a
+    // factor along with them.
+    if metallic_roughness_texture.is_some() {
+        if material.unknown_param.contains_key("map_Pm") {
+            metallic = material_param(material, "Pm").unwrap_or(1.0);
+        }
+        if material.unknown_param.contains_key("map_Pr") {
+            roughness = material_param(material, "Pr").unwrap_or(1.0);
+        }
+    }
+    let emissive_texture = context.texture(material, TextureSlot::Emissive);
+    // Bevy multiplies the emissive texture by the emissive color, which is black by default, so a
+    // `map_Ke` without a `Ke` would never glow.
+    let default_emissive = match emissive_texture {
+        Some(_) => Color::WHITE,
+        None => defaults.emissive,
+    };
+    let emissive = material_color_param(material, "Ke")
+        .map_or(default_emissive, |[r, g, b]| Color::rgb(r, g, b));
+    let mut reflectance =
+        specular_to_reflectance(material.specular).unwrap_or(defaults.reflectance);
+    let mut unlit = false;
+    if context.settings().illumination_models {
+        match material.illumination_model {
+            // Color only, with at most a flat ambient term, as used for skies and billboards.
+            Some(0) | Some(1) => unlit = true,
+            // Every model from 3 on adds ray traced reflections, which is as close as Bevy gets.
+            // Metallic is left to `Ks` and `Pm`, as the models also cover glass and varnish,
+            // which reflect without being metals.
+            Some(3..=8) => reflectance = 1.0,
+            _ => {}
+        }
+    }
+
+    StandardMaterial {
+        base_color: material_color(material),
+        base_color_texture: context.texture(material, TextureSlot::Diffuse),
+        roughness,
+        metallic,
+        metallic_roughness_texture,
+        reflectance,
+        normal_map: context.texture(material, TextureSlot::Normal),
+        occlusion_texture: context.texture(material, TextureSlot::Ambient),
+        emissive,
+        emissive_texture,
+        unlit,
+        ..Default::default()
+    }
+}
+
+// MTL files describe surfaces in one of two ways. The PBR extension adds `Pm` metallic and `Pr`
+// roughness, while legacy files describe a Blinn-Phong surface with a `Ks` specular color and an
+// `Ns` exponent. A material giving `Pm` or `Pr` is taken as PBR and used as is, with `Ns` standing
+// in for a missing `Pr`. Otherwise the roughness comes from `Ns`, and as dielectrics have an
+// untinted specular, the saturation of `Ks` is taken as how metallic the surface is. `map_Ks`
+// holds specular colors rather than metallic and roughness, so neither way uses it. The sheen,
+// clearcoat and anisotropy parameters of the extension have no counterpart in Bevy's materials
+// and are ignored.
+fn metallic_roughness(material: &tobj::Material, settings: &ObjLoaderSettings) -> (f32, f32) {
+    let metallic = material_param(material, "Pm");
+    let roughness = material_param(material, "Pr");
+    let shininess_roughness = settings.shininess_conversion.roughness(material.shininess);
+    if metallic.is_some() || roughness.is_some() {
+        return (
+            metallic.unwrap_or(StandardMaterial::default().metallic),
+            roughness.unwrap_or(shininess_roughness),
+        );
+    }
+
+    let [r, g, b] = material.specular;
+    let max = r.max(g).max(b);
+    let saturation = if max > 0.0 {
+        (max - r.min(g).min(b)) / max
+    } else {
+        0.0
+    };
+    (saturation, shininess_roughness)
+}
+
+// Bevy has no alpha map slot, but a `map_d` still means the surface is meant to be see-through, so
+// it is rendered in the transparent pass with the base color alpha.
+fn is_transparent_material(material: &tobj::Material) -> bool {
+    material_alpha(material) < 1.0 || !material.dissolve_texture.is_empty()
+}
+
+fn material_color(material: &tobj::Material) -> Color {
+    let [r, g, b] = material.diffuse;
+    Color::rgba(r, g, b, material_alpha(material))
+}
+
+// tobj parses `d` into `dissolve` (defaulting to opaque), while the inverted `Tr` convention is
+// left in the unknown parameters and only used when no dissolve was given.
+fn material_alpha(material: &tobj::Material) -> f32 {
+    match material_param(material, "Tr") {
+        Some(transparency) if material.dissolve >= 1.0 => 1.0 - transparency,
+        _ => material.dissolve,
+    }
+    .clamp(0.0, 1.0)
+}
+
+/// Converts an MTL `Ks` specular color into Bevy's dielectric reflectance by taking its
+/// luminance, so the `Ks 0.5 0.5 0.5` most exporters write maps to Bevy's default of 0.5.
+/// Returns `None` for black, which is also what tobj reports when `Ks` is missing.
+pub fn specular_to_reflectance(specular: [f32; 3]) -> Option<f32> {
+    let [r, g, b] = specular;
+    let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    if luminance > 0.0 {
+        Some(luminance.min(1.0))
+    } else {
+        None
+    }
+}
+
+fn material_param(material: &tobj::Material, key: &str) -> Option<f32> {
+    material
+        .unknown_param
+        .get(key)
+        .and_then(|value| value.trim().parse().ok())
+}
+
+fn material_color_param(material: &tobj::Material, key: &str) -> Option<[f32; 3]> {
+    let value = material.unknown_param.get(key)?;
+    let mut components = value.split_whitespace().map(|c| c.parse::<f32>());
+    match (components.next(), components.next(), components.next()) {
+        (Some(Ok(r)), Some(Ok(g)), Some(Ok(b))) => Some([r, g, b]),
+        _ => None,
+    }
+}
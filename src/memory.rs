@@ -0,0 +1,125 @@
+use bevy::app::{AppBuilder, Plugin};
+use bevy::asset::{create_platform_default_asset_io, AssetIo, AssetIoError, AssetServer};
+use bevy::tasks::IoTaskPool;
+use bevy::utils::BoxedFuture;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+type Resolver = dyn Fn(&Path) -> Option<Vec<u8>> + Send + Sync;
+
+/// Files that only exist in memory, like OBJ text generated at runtime, which the asset server
+/// loads as if they were in the asset folder. Material libraries and textures they reference
+/// are looked up the same way, so generated files can use both generated and regular assets.
+///
+/// Inserted as a resource by `MemoryAssetPlugin`, and cheap to clone as every clone shares the
+/// same files.
+#[derive(Clone, Default)]
+pub struct MemoryAssets {
+    files: Arc<RwLock<HashMap<PathBuf, Vec<u8>>>>,
+    resolver: Option<Arc<Resolver>>,
+}
+
+impl MemoryAssets {
+    /// Looks files that weren't inserted up through `resolver` before reading them from the
+    /// asset folder.
+    pub fn with_resolver(
+        mut self,
+        resolver: impl Fn(&Path) -> Option<Vec<u8>> + Send + Sync + 'static,
+    ) -> Self {
+        self.resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Adds a file under `path`, relative to the asset folder, replacing any earlier one. Assets
+    /// already loaded from it aren't reloaded.
+    pub fn insert(&self, path: impl Into<PathBuf>, bytes: Vec<u8>) {
+        self.files.write().unwrap().insert(path.into(), bytes);
+    }
+
+    /// Removes the file under `path` and returns its bytes, after which the path is looked up like
+    /// any other file that wasn't inserted. Assets already loaded from it stay loaded.
+    pub fn remove(&self, path: &Path) -> Option<Vec<u8>> {
+        self.files.write().unwrap().remove(path)
+    }
+
+    fn read(&self, path: &Path) -> Option<Vec<u8>> {
+        if let Some(bytes) = self.files.read().unwrap().get(path) {
+            return Some(bytes.clone());
+        }
+        self.resolver.as_ref().and_then(|resolver| resolver(path))
+    }
+}
+
+/// An `AssetIo` serving `MemoryAssets` ahead of another `AssetIo`.
+pub struct MemoryAssetIo {
+    assets: MemoryAssets,
+    fallback: Box<dyn AssetIo>,
+}
+
+impl MemoryAssetIo {
+    pub fn new(assets: MemoryAssets, fallback: Box<dyn AssetIo>) -> Self {
+        MemoryAssetIo { assets, fallback }
+    }
+}
+
+impl AssetIo for MemoryAssetIo {
+    fn load_path<'a>(&'a self, path: &'a Path) -> BoxedFuture<'a, Result<Vec<u8>, AssetIoError>> {
+        match self.assets.read(path) {
+            Some(bytes) => Box::pin(async move { Ok(bytes) }),
+            None => self.fallback.load_path(path),
+        }
+    }
+
+    fn read_directory(
+        &self,
+        path: &Path,
+    ) -> Result<Box<dyn Iterator<Item = PathBuf>>, AssetIoError> {
+        self.fallback.read_directory(path)
+    }
+
+    fn is_directory(&self, path: &Path) -> bool {
+        self.fallback.is_directory(path)
+    }
+
+    fn watch_path_for_changes(&self, path: &Path) -> Result<(), AssetIoError> {
+        // Files in memory don't change behind the app's back.
+        if self.assets.files.read().unwrap().contains_key(path) {
+            return Ok(());
+        }
+        self.fallback.watch_path_for_changes(path)
+    }
+
+    fn watch_for_changes(&self) -> Result<(), AssetIoError> {
+        self.fallback.watch_for_changes()
+    }
+}
+
+/// Replaces the asset server with one reading `MemoryAssets` ahead of the asset folder. It has to
+/// be added after `CorePlugin` and before `AssetPlugin`, which only creates an asset server when
+/// there is none yet:
+///
+/// ```ignore
+/// App::build().add_plugins_with(DefaultPlugins, |group| {
+///     group.add_before::<AssetPlugin, _>(MemoryAssetPlugin::default())
+/// })
+/// ```
+#[derive(Default)]
+pub struct MemoryAssetPlugin {
+    pub assets: MemoryAssets,
+}
+
+impl Plugin for MemoryAssetPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let task_pool = app
+            .world()
+            .get_resource::<IoTaskPool>()
+            .expect("`IoTaskPool` resource not found, add `MemoryAssetPlugin` after `CorePlugin`")
+            .0
+            .clone();
+        let fallback = create_platform_default_asset_io(app);
+        let asset_io = MemoryAssetIo::new(self.assets.clone(), fallback);
+        app.insert_resource(AssetServer::new(asset_io, task_pool))
+            .insert_resource(self.assets.clone());
+    }
+}
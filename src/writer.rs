@@ -0,0 +1,146 @@
+use bevy::render::mesh::{Indices, Mesh, VertexAttributeValues};
+use bevy::render::pipeline::PrimitiveTopology;
+use std::io::{self, Write};
+
+/// Writes the positions, normals, texcoords and indices of `mesh` as an OBJ file.
+///
+/// Triangle lists are written as `f` faces, line lists as `l` segments and point lists as `p`
+/// points. Texcoords are flipped back to the bottom-left origin of OBJ, undoing the flip of the
+/// default `ObjLoaderSettings::flip_uv_v`, so that loading the written file with the default
+/// settings yields the same mesh. Meshes without positions or with another topology are rejected
+/// with `io::ErrorKind::InvalidInput`.
+pub fn write_obj(mesh: &Mesh, writer: &mut impl Write) -> io::Result<()> {
+    let element = match mesh.primitive_topology() {
+        PrimitiveTopology::TriangleList => "f",
+        PrimitiveTopology::LineList => "l",
+        PrimitiveTopology::PointList => "p",
+        _ => return Err(invalid_input("only list topologies can be written")),
+    };
+    let positions = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+        Some(VertexAttributeValues::Float3(positions)) => positions,
+        _ => return Err(invalid_input("mesh has no positions")),
+    };
+    let normals = match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+        Some(VertexAttributeValues::Float3(normals)) => Some(normals),
+        _ => None,
+    };
+    let texcoords = match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+        Some(VertexAttributeValues::Float2(texcoords)) => Some(texcoords),
+        _ => None,
+    };
+
+    for [x, y, z] in positions {
+        writeln!(writer, "v {} {} {}", x, y, z)?;
+    }
+    for [u, v] in texcoords.into_iter().flatten() {
+        writeln!(writer, "vt {} {}", u, 1.0 - v)?;
+    }
+    for [x, y, z] in normals.into_iter().flatten() {
+        writeln!(writer, "vn {} {} {}", x, y, z)?;
+    }
+
+    let indices: Vec<usize> = match mesh.indices() {
+        Some(Indices::U16(indices)) => indices.iter().map(|&i| i as usize).collect(),
+        Some(Indices::U32(indices)) => indices.iter().map(|&i| i as usize).collect(),
+        None => (0..positions.len()).collect(),
+    };
+    let vertices_per_element = match element {
+        "f" => 3,
+        "l" => 2,
+        _ => 1,
+    };
+    for primitive in indices.chunks_exact(vertices_per_element) {
+        write!(writer, "{}", element)?;
+        for &index in primitive {
+            // OBJ indices start at one.
+            let index = index + 1;
+            match (texcoords.is_some(), normals.is_some()) {
+                // Points only reference positions.
+                _ if element == "p" => write!(writer, " {}", index)?,
+                (true, true) => write!(writer, " {}/{}/{}", index, index, index)?,
+                (true, false) => write!(writer, " {}/{}", index, index)?,
+                (false, true) => write!(writer, " {}//{}", index, index)?,
+                (false, false) => write!(writer, " {}", index)?,
+            }
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_obj, ObjLoaderSettings};
+    use std::path::Path;
+
+    fn parse(obj: &[u8]) -> Mesh {
+        let settings = ObjLoaderSettings::default();
+        let mut parsed = parse_obj(obj, Path::new("model.obj"), |_| None, &settings).unwrap();
+        parsed.meshes.remove(0).mesh
+    }
+
+    fn round_trip(mesh: &Mesh) -> Mesh {
+        let mut obj = Vec::new();
+        write_obj(mesh, &mut obj).unwrap();
+        parse(&obj)
+    }
+
+    fn indices(mesh: &Mesh) -> Vec<u32> {
+        match mesh.indices() {
+            Some(Indices::U16(indices)) => indices.iter().map(|&i| i as u32).collect(),
+            Some(Indices::U32(indices)) => indices.clone(),
+            None => panic!("mesh has no indices"),
+        }
+    }
+
+    fn assert_same_geometry(written: &Mesh, read: &Mesh) {
+        for name in [
+            Mesh::ATTRIBUTE_POSITION,
+            Mesh::ATTRIBUTE_NORMAL,
+            Mesh::ATTRIBUTE_UV_0,
+        ] {
+            let bytes = |mesh: &Mesh| {
+                mesh.attribute(name)
+                    .map(|values| values.get_bytes().to_vec())
+            };
+            assert_eq!(bytes(read), bytes(written), "{}", name);
+        }
+        assert_eq!(indices(read), indices(written));
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let mesh = parse(
+            b"v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 0.5\nvn 0 0 1\n\
+              f 1/1/1 2/2/1 3/3/1 4/4/1\n",
+        );
+        assert!(matches!(mesh.indices(), Some(Indices::U16(_))));
+        assert_same_geometry(&mesh, &round_trip(&mesh));
+    }
+
+    #[test]
+    fn writes_32_bit_indices() {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        let positions = vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; 3]);
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_UV_0,
+            vec![[0.0, 1.0], [1.0, 1.0], [0.0, 0.0]],
+        );
+        mesh.set_indices(Some(Indices::U32(vec![0, 1, 2])));
+        assert_same_geometry(&mesh, &round_trip(&mesh));
+    }
+
+    #[test]
+    fn rejects_meshes_without_positions() {
+        let mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        let error = write_obj(&mesh, &mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}